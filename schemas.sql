@@ -1,10 +1,233 @@
+CREATE TABLE IF NOT EXISTS events(
+    id varchar(32) PRIMARY KEY UNIQUE NOT NULL,
+    name varchar(64) NOT NULL,
+    starts_at timestamptz NOT NULL,
+    ends_at timestamptz NOT NULL,
+    questions varchar(256) NOT NULL DEFAULT 'questions',
+    redeem_until timestamptz
+);
+
 CREATE TABLE IF NOT EXISTS users(
-    card_hash varchar(64) PRIMARY KEY UNIQUE NOT NULL,
+    card_hash varchar(64) NOT NULL,
     id UUID UNIQUE NOT NULL,
-    username varchar(32) NOT NULL
+    username varchar(32) NOT NULL,
+    telegram_id bigint,
+    event_id varchar(32) NOT NULL,
+    PRIMARY KEY (event_id, card_hash),
+    UNIQUE (event_id, username),
+    UNIQUE (event_id, telegram_id)
 );
 
 CREATE TABLE IF NOT EXISTS users_reg(
-    hash varchar(64) PRIMARY KEY UNIQUE NOT NULL,
-    id UUID UNIQUE NOT NULL
-)
\ No newline at end of file
+    hash varchar(64) NOT NULL,
+    id UUID UNIQUE NOT NULL,
+    event_id varchar(32) NOT NULL,
+    PRIMARY KEY (event_id, hash)
+);
+
+CREATE TABLE IF NOT EXISTS question_instances(
+    id UUID PRIMARY KEY UNIQUE NOT NULL,
+    bound_to UUID NOT NULL,
+    category varchar(64) NOT NULL,
+    question_key varchar(64) NOT NULL,
+    question jsonb NOT NULL,
+    issued_at timestamptz NOT NULL DEFAULT now(),
+    answered_at timestamptz,
+    expires_at timestamptz,
+    station_id varchar(32),
+    hints_used integer NOT NULL DEFAULT 0,
+    fifty_fifty_used boolean NOT NULL DEFAULT false
+);
+
+CREATE TABLE IF NOT EXISTS answers(
+    question_id UUID PRIMARY KEY UNIQUE NOT NULL,
+    user_id UUID NOT NULL,
+    category varchar(64) NOT NULL,
+    question_key varchar(64) NOT NULL,
+    correct boolean NOT NULL,
+    points integer NOT NULL,
+    answered_at timestamptz NOT NULL DEFAULT now(),
+    event_id varchar(32) NOT NULL
+);
+
+CREATE TABLE IF NOT EXISTS question_history(
+    user_id UUID NOT NULL,
+    category varchar(64) NOT NULL,
+    question_key varchar(64) NOT NULL,
+    served_at timestamptz NOT NULL DEFAULT now(),
+    PRIMARY KEY (user_id, category, question_key)
+);
+
+CREATE TABLE IF NOT EXISTS stations(
+    id varchar(32) PRIMARY KEY UNIQUE NOT NULL,
+    name varchar(64) NOT NULL,
+    categories varchar(64)[] NOT NULL,
+    active_from timestamptz,
+    active_until timestamptz
+);
+
+CREATE TABLE IF NOT EXISTS station_visits(
+    id UUID PRIMARY KEY UNIQUE NOT NULL,
+    station_id varchar(32) NOT NULL,
+    user_id UUID NOT NULL,
+    visited_at timestamptz NOT NULL DEFAULT now()
+);
+
+CREATE TABLE IF NOT EXISTS quest_progress(
+    user_id UUID PRIMARY KEY UNIQUE NOT NULL,
+    stage varchar(32) NOT NULL,
+    stage_started_at timestamptz NOT NULL DEFAULT now(),
+    stage_completed_at timestamptz,
+    completed_stages varchar(32)[] NOT NULL DEFAULT '{}',
+    finished_at timestamptz
+);
+
+CREATE TABLE IF NOT EXISTS teams(
+    id UUID PRIMARY KEY UNIQUE NOT NULL,
+    name varchar(32) NOT NULL,
+    code varchar(8) UNIQUE NOT NULL,
+    created_at timestamptz NOT NULL DEFAULT now(),
+    event_id varchar(32) NOT NULL,
+    UNIQUE (event_id, name)
+);
+
+CREATE TABLE IF NOT EXISTS team_members(
+    user_id UUID PRIMARY KEY UNIQUE NOT NULL,
+    team_id UUID NOT NULL,
+    captain boolean NOT NULL DEFAULT false,
+    joined_at timestamptz NOT NULL DEFAULT now()
+);
+
+CREATE TABLE IF NOT EXISTS user_achievements(
+    user_id UUID NOT NULL,
+    achievement varchar(64) NOT NULL,
+    earned_at timestamptz NOT NULL DEFAULT now(),
+    PRIMARY KEY (user_id, achievement)
+);
+
+CREATE TABLE IF NOT EXISTS rewards(
+    id varchar(32) NOT NULL,
+    event_id varchar(32) NOT NULL,
+    name varchar(64) NOT NULL,
+    description text,
+    cost integer NOT NULL,
+    stock integer NOT NULL,
+    PRIMARY KEY (event_id, id)
+);
+
+CREATE TABLE IF NOT EXISTS redemptions(
+    id UUID PRIMARY KEY UNIQUE NOT NULL,
+    user_id UUID NOT NULL,
+    reward_id varchar(32) NOT NULL,
+    cost integer NOT NULL,
+    code varchar(16) UNIQUE NOT NULL,
+    redeemed_at timestamptz NOT NULL DEFAULT now(),
+    verified_at timestamptz,
+    verified_by varchar(64),
+    event_id varchar(32) NOT NULL
+);
+
+CREATE TABLE IF NOT EXISTS streaks(
+    user_id UUID NOT NULL,
+    category varchar(64) NOT NULL,
+    current integer NOT NULL,
+    best integer NOT NULL,
+    PRIMARY KEY (user_id, category)
+);
+
+CREATE TABLE IF NOT EXISTS lifelines(
+    user_id UUID PRIMARY KEY NOT NULL,
+    fifty_fifty integer NOT NULL,
+    skip integer NOT NULL
+);
+
+-- Upgrades databases created before events existed. Their data is moved into a `default` event,
+-- adjust its times and question folder afterwards. Does nothing on databases that are up to date.
+DO $$
+BEGIN
+    IF EXISTS (SELECT 1 FROM information_schema.tables t
+               WHERE t.table_schema = current_schema()
+               AND t.table_name IN ('users', 'users_reg', 'answers', 'teams')
+               AND NOT EXISTS (SELECT 1 FROM information_schema.columns c
+                               WHERE c.table_schema = t.table_schema AND c.table_name = t.table_name
+                               AND c.column_name = 'event_id')) THEN
+        INSERT INTO events VALUES ('default', 'Default event', now(), now() + interval '1 year', 'questions')
+        ON CONFLICT DO NOTHING;
+    END IF;
+
+    IF NOT EXISTS (SELECT 1 FROM information_schema.columns
+                   WHERE table_schema = current_schema() AND table_name = 'users' AND column_name = 'event_id') THEN
+        ALTER TABLE users ADD COLUMN event_id varchar(32) NOT NULL DEFAULT 'default';
+        ALTER TABLE users ALTER COLUMN event_id DROP DEFAULT;
+        ALTER TABLE users DROP CONSTRAINT IF EXISTS users_card_hash_key;
+        ALTER TABLE users DROP CONSTRAINT users_pkey;
+        ALTER TABLE users ADD PRIMARY KEY (event_id, card_hash);
+        ALTER TABLE users ADD UNIQUE (event_id, username);
+    END IF;
+
+    IF NOT EXISTS (SELECT 1 FROM information_schema.columns
+                   WHERE table_schema = current_schema() AND table_name = 'users_reg' AND column_name = 'event_id') THEN
+        ALTER TABLE users_reg ADD COLUMN event_id varchar(32) NOT NULL DEFAULT 'default';
+        ALTER TABLE users_reg ALTER COLUMN event_id DROP DEFAULT;
+        ALTER TABLE users_reg DROP CONSTRAINT IF EXISTS users_reg_hash_key;
+        ALTER TABLE users_reg DROP CONSTRAINT users_reg_pkey;
+        ALTER TABLE users_reg ADD PRIMARY KEY (event_id, hash);
+    END IF;
+
+    IF NOT EXISTS (SELECT 1 FROM information_schema.columns
+                   WHERE table_schema = current_schema() AND table_name = 'answers' AND column_name = 'event_id') THEN
+        ALTER TABLE answers ADD COLUMN event_id varchar(32) NOT NULL DEFAULT 'default';
+        ALTER TABLE answers ALTER COLUMN event_id DROP DEFAULT;
+    END IF;
+
+    IF NOT EXISTS (SELECT 1 FROM information_schema.columns
+                   WHERE table_schema = current_schema() AND table_name = 'teams' AND column_name = 'event_id') THEN
+        ALTER TABLE teams ADD COLUMN event_id varchar(32) NOT NULL DEFAULT 'default';
+        ALTER TABLE teams ALTER COLUMN event_id DROP DEFAULT;
+        ALTER TABLE teams DROP CONSTRAINT IF EXISTS teams_name_key;
+        ALTER TABLE teams ADD UNIQUE (event_id, name);
+    END IF;
+END $$;
+
+-- Upgrades databases created before users could register through telegram
+ALTER TABLE users ADD COLUMN IF NOT EXISTS telegram_id bigint;
+DO $$
+BEGIN
+    IF NOT EXISTS (SELECT 1 FROM pg_constraint WHERE conname = 'users_event_id_telegram_id_key') THEN
+        ALTER TABLE users DROP CONSTRAINT IF EXISTS users_telegram_id_key;
+        ALTER TABLE users ADD UNIQUE (event_id, telegram_id);
+    END IF;
+END $$;
+
+-- Upgrades databases created before rewards could be redeemed after the end of an event
+ALTER TABLE events ADD COLUMN IF NOT EXISTS redeem_until timestamptz;
+
+-- Upgrades databases that remembered served questions by their position in the category file. The
+-- keys are taken from the issued questions, history of questions that were skipped is lost.
+DO $$
+BEGIN
+    IF EXISTS (SELECT 1 FROM information_schema.columns
+               WHERE table_schema = current_schema() AND table_name = 'question_instances' AND column_name = 'question_index') THEN
+        ALTER TABLE question_instances ADD COLUMN question_key varchar(64);
+        UPDATE question_instances SET question_key = COALESCE(question->>'id', encode(sha256(convert_to(
+            btrim(regexp_replace(question->>'question', '\s+', ' ', 'g')), 'UTF8')), 'hex'));
+        ALTER TABLE question_instances ALTER COLUMN question_key SET NOT NULL;
+
+        ALTER TABLE answers ADD COLUMN question_key varchar(64);
+        UPDATE answers a SET question_key = q.question_key FROM question_instances q WHERE q.id = a.question_id;
+        UPDATE answers SET question_key = 'index-' || question_index WHERE question_key IS NULL;
+        ALTER TABLE answers ALTER COLUMN question_key SET NOT NULL;
+        ALTER TABLE answers DROP COLUMN question_index;
+
+        ALTER TABLE question_history ADD COLUMN question_key varchar(64);
+        UPDATE question_history h SET question_key = q.question_key FROM question_instances q
+        WHERE q.bound_to = h.user_id AND q.category = h.category AND q.question_index = h.question_index;
+        DELETE FROM question_history WHERE question_key IS NULL;
+        ALTER TABLE question_history ALTER COLUMN question_key SET NOT NULL;
+        ALTER TABLE question_history DROP CONSTRAINT question_history_pkey;
+        ALTER TABLE question_history DROP COLUMN question_index;
+        ALTER TABLE question_history ADD PRIMARY KEY (user_id, category, question_key);
+
+        ALTER TABLE question_instances DROP COLUMN question_index;
+    END IF;
+END $$;
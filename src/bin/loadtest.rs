@@ -0,0 +1,248 @@
+//! Load test for the quiz endpoints of a running server.
+//!
+//! Seeds synthetic users straight into Postgres, then lets `--concurrency` workers request and
+//! answer questions for them until the category is exhausted, and reports the achieved throughput.
+//! The seeded users and everything they produced are removed afterwards.
+//!
+//! Usage: `DATABASE_URL=postgres://... cargo run --release --bin loadtest -- [--server URL]
+//! [--event ID] [--category NAME] [--users N] [--concurrency N]`. Users are seeded into the
+//! event that is currently running unless `--event` is given.
+
+use hyper::client::HttpConnector;
+use hyper::{Body, Client, Method, Request};
+use serde_json::{json, Value};
+use sqlx::PgPool;
+use std::time::{Duration, Instant};
+use uuid::Uuid;
+
+struct Options {
+    database_url: String,
+    server: String,
+    event: Option<String>,
+    category: String,
+    users: usize,
+    concurrency: usize,
+}
+
+fn parse_options() -> anyhow::Result<Options> {
+    let mut options = Options {
+        database_url: std::env::var("DATABASE_URL")
+            .map_err(|_| anyhow::Error::msg("DATABASE_URL has to be set"))?,
+        server: "http://127.0.0.1:4040".to_owned(),
+        event: None,
+        category: "test1".to_owned(),
+        users: 200,
+        concurrency: 32,
+    };
+    let mut args = std::env::args().skip(1);
+    while let Some(flag) = args.next() {
+        let value = args
+            .next()
+            .ok_or_else(|| anyhow::Error::msg(format!("Missing value for `{flag}`")))?;
+        match flag.as_str() {
+            "--server" => options.server = value,
+            "--event" => options.event = Some(value),
+            "--category" => options.category = value,
+            "--users" => options.users = value.parse()?,
+            "--concurrency" => options.concurrency = value.parse::<usize>()?.max(1),
+            _ => anyhow::bail!("Unknown argument `{flag}`"),
+        }
+    }
+    Ok(options)
+}
+
+#[derive(Default)]
+struct WorkerStats {
+    latencies: Vec<Duration>,
+    errors: usize,
+}
+
+async fn request(
+    client: &Client<HttpConnector>,
+    method: Method,
+    uri: String,
+    body: Option<Value>,
+) -> anyhow::Result<(Value, Duration)> {
+    let started = Instant::now();
+    let request = Request::builder()
+        .method(method)
+        .uri(uri)
+        .header("content-type", "application/json")
+        .body(body.map_or_else(Body::empty, |body| Body::from(body.to_string())))?;
+    let response = client.request(request).await?;
+    let bytes = hyper::body::to_bytes(response.into_body()).await?;
+    Ok((serde_json::from_slice(&bytes)?, started.elapsed()))
+}
+
+/// Any answer of the right type, correctness does not matter here
+fn dummy_answer(kind: &str) -> Value {
+    match kind {
+        "multiple" | "ordering" => json!({ "type": kind, "answer": [0] }),
+        "text" => json!({ "type": kind, "answer": "" }),
+        _ => json!({ "type": kind, "answer": 0 }),
+    }
+}
+
+async fn run_worker(
+    client: Client<HttpConnector>,
+    server: String,
+    category: String,
+    users: Vec<Uuid>,
+) -> WorkerStats {
+    let mut stats = WorkerStats::default();
+    for user in users {
+        loop {
+            let uri = format!("{server}/user/{user}/question/{category}");
+            let question = match request(&client, Method::GET, uri, None).await {
+                Ok((question, latency)) => {
+                    stats.latencies.push(latency);
+                    question
+                }
+                Err(err) => {
+                    log_error(&err.to_string());
+                    stats.errors += 1;
+                    break;
+                }
+            };
+            if question["success"] != json!(true) {
+                let error = question["error"].as_str().unwrap_or_default();
+                if !error.contains("is complete") {
+                    log_error(error);
+                    stats.errors += 1;
+                }
+                break;
+            }
+
+            let id = question["id"].as_str().unwrap_or_default();
+            let kind = question["type"].as_str().unwrap_or_default();
+            let uri = format!("{server}/user/{user}/answer/{id}");
+            match request(&client, Method::POST, uri, Some(dummy_answer(kind))).await {
+                Ok((result, latency)) => {
+                    stats.latencies.push(latency);
+                    if result["success"] != json!(true) {
+                        log_error(result["error"].as_str().unwrap_or_default());
+                        stats.errors += 1;
+                    }
+                }
+                Err(err) => {
+                    log_error(&err.to_string());
+                    stats.errors += 1;
+                }
+            }
+        }
+    }
+    stats
+}
+
+fn log_error(error: &str) {
+    eprintln!("Request failed: {error}");
+}
+
+async fn running_event(pool: &PgPool) -> anyhow::Result<String> {
+    sqlx::query_scalar(
+        "SELECT id FROM events WHERE starts_at <= now() AND now() < ends_at \
+         ORDER BY starts_at DESC LIMIT 1",
+    )
+    .fetch_optional(pool)
+    .await?
+    .ok_or_else(|| anyhow::Error::msg("There is no running event, pass one with `--event`"))
+}
+
+async fn seed_users(pool: &PgPool, event: &str, count: usize) -> anyhow::Result<Vec<Uuid>> {
+    let mut users = Vec::with_capacity(count);
+    for _ in 0..count {
+        let id = Uuid::new_v4();
+        sqlx::query("INSERT INTO users(card_hash, id, username, event_id) VALUES ($1, $2, $3, $4)")
+            .bind(format!("{:064x}", id.as_u128()))
+            .bind(id)
+            .bind(format!("loadtest-{}", &id.simple().to_string()[..8]))
+            .bind(event)
+            .execute(pool)
+            .await?;
+        users.push(id);
+    }
+    Ok(users)
+}
+
+async fn cleanup(pool: &PgPool, users: &[Uuid]) -> anyhow::Result<()> {
+    for query in [
+        "DELETE FROM answers WHERE user_id = ANY($1)",
+        "DELETE FROM question_history WHERE user_id = ANY($1)",
+        "DELETE FROM question_instances WHERE bound_to = ANY($1)",
+        "DELETE FROM user_achievements WHERE user_id = ANY($1)",
+        "DELETE FROM streaks WHERE user_id = ANY($1)",
+        "DELETE FROM lifelines WHERE user_id = ANY($1)",
+        "DELETE FROM users WHERE id = ANY($1)",
+    ] {
+        sqlx::query(query).bind(users).execute(pool).await?;
+    }
+    Ok(())
+}
+
+#[tokio::main]
+async fn main() -> anyhow::Result<()> {
+    let options = parse_options()?;
+    let pool = PgPool::connect(&options.database_url).await?;
+
+    let event = match options.event.clone() {
+        Some(event) => event,
+        None => running_event(&pool).await?,
+    };
+    println!("Seeding {} users into event `{event}`...", options.users);
+    let users = seed_users(&pool, &event, options.users).await?;
+
+    println!(
+        "Running against {} with {} concurrent workers...",
+        options.server, options.concurrency
+    );
+    let client = Client::new();
+    let started = Instant::now();
+    let workers = (0..options.concurrency)
+        .map(|worker| {
+            let batch = users
+                .iter()
+                .skip(worker)
+                .step_by(options.concurrency)
+                .copied()
+                .collect::<Vec<Uuid>>();
+            tokio::spawn(run_worker(
+                client.clone(),
+                options.server.clone(),
+                options.category.clone(),
+                batch,
+            ))
+        })
+        .collect::<Vec<_>>();
+
+    let mut latencies = Vec::new();
+    let mut errors = 0;
+    for worker in workers {
+        let stats = worker.await?;
+        latencies.extend(stats.latencies);
+        errors += stats.errors;
+    }
+    let elapsed = started.elapsed();
+
+    cleanup(&pool, &users).await?;
+
+    latencies.sort_unstable();
+    let percentile = |p: f64| {
+        latencies
+            .get(((latencies.len() as f64 * p) as usize).min(latencies.len().saturating_sub(1)))
+            .copied()
+            .unwrap_or_default()
+    };
+    println!("Requests:   {}", latencies.len());
+    println!("Errors:     {errors}");
+    println!("Elapsed:    {:.2?}", elapsed);
+    println!(
+        "Throughput: {:.1} req/s",
+        latencies.len() as f64 / elapsed.as_secs_f64()
+    );
+    println!(
+        "Latency:    p50 {:.2?}, p99 {:.2?}",
+        percentile(0.5),
+        percentile(0.99)
+    );
+    Ok(())
+}
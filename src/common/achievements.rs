@@ -0,0 +1,255 @@
+use crate::common::bank::BankSnapshot;
+use crate::common::questions::QuizResult;
+use crate::common::scores::category_scores;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::{FromRow, PgPool};
+use std::collections::{BTreeMap, HashMap};
+use uuid::Uuid;
+
+/// Achievement as declared in the achievements file of the question folder
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Achievement {
+    pub title: String,
+    #[serde(default)]
+    pub description: Option<String>,
+    #[serde(default)]
+    pub icon: Option<String>,
+    pub rule: Rule,
+}
+
+/// Condition that has to hold after an answer for the achievement to be earned
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Rule {
+    /// At least `count` correct answers, only counting `category` if it is given
+    CorrectAnswers {
+        count: i64,
+        #[serde(default)]
+        category: Option<String>,
+    },
+    /// `count` correct answers in a row
+    Streak { count: i64 },
+    /// At least `count` points in total
+    Points { count: i64 },
+    /// Every question of `categories` served, or of all categories if none are listed. Questions
+    /// that expired or were skipped count as well, since they are never served again.
+    CompletedCategories {
+        #[serde(default)]
+        categories: Vec<String>,
+    },
+    /// A correct answer given at most `seconds` after the question was issued
+    FastAnswer { seconds: f64 },
+}
+
+pub fn validate_achievements(achievements: &BTreeMap<String, Achievement>) -> Vec<String> {
+    let mut errors = Vec::new();
+    for (id, achievement) in achievements {
+        let valid = match &achievement.rule {
+            Rule::CorrectAnswers { count, .. }
+            | Rule::Streak { count }
+            | Rule::Points { count } => *count > 0,
+            Rule::CompletedCategories { .. } => true,
+            Rule::FastAnswer { seconds } => *seconds > 0.0,
+        };
+        if !valid {
+            errors.push(format!(
+                "Achievement `{id}` has a rule that can never be met"
+            ));
+        }
+    }
+    errors
+}
+
+/// Achievement along with the time a user earned it, if they did
+#[derive(Debug, Clone, Serialize)]
+pub struct AchievementInfo {
+    pub id: String,
+    pub title: String,
+    pub description: Option<String>,
+    pub icon: Option<String>,
+    pub earned_at: Option<DateTime<Utc>>,
+}
+
+impl AchievementInfo {
+    fn new(
+        id: String,
+        achievement: Option<&Achievement>,
+        earned_at: Option<DateTime<Utc>>,
+    ) -> Self {
+        Self {
+            title: achievement
+                .map(|achievement| achievement.title.clone())
+                .unwrap_or_else(|| id.clone()),
+            description: achievement.and_then(|achievement| achievement.description.clone()),
+            icon: achievement.and_then(|achievement| achievement.icon.clone()),
+            earned_at,
+            id,
+        }
+    }
+}
+
+#[derive(Debug, Clone, FromRow)]
+struct StoredAchievement {
+    achievement: String,
+    earned_at: DateTime<Utc>,
+}
+
+/// The answer that triggered an evaluation
+#[derive(Debug, Clone)]
+pub struct AnsweredQuestion {
+    pub correct: bool,
+    /// Seconds between issuing and answering the question
+    pub seconds: f64,
+}
+
+struct UserStats {
+    correct: HashMap<String, i64>,
+    /// Questions served in each category, whether they were answered or not
+    served: HashMap<String, i64>,
+    points: i64,
+    streak: i64,
+}
+
+impl Rule {
+    fn is_met(&self, stats: &UserStats, snapshot: &BankSnapshot, last: &AnsweredQuestion) -> bool {
+        match self {
+            Rule::CorrectAnswers {
+                count,
+                category: Some(category),
+            } => stats.correct.get(category).copied().unwrap_or(0) >= *count,
+            Rule::CorrectAnswers {
+                count,
+                category: None,
+            } => stats.correct.values().sum::<i64>() >= *count,
+            Rule::Streak { count } => stats.streak >= *count,
+            Rule::Points { count } => stats.points >= *count,
+            Rule::CompletedCategories { categories } => {
+                let questions = |category: &String| {
+                    snapshot
+                        .categories
+                        .get(category)
+                        .and_then(|loaded| loaded.questions.as_ref())
+                        .map(|questions| questions.len() as i64)
+                };
+                let completed = |category: &String| {
+                    questions(category).is_some_and(|questions| {
+                        stats.served.get(category).copied().unwrap_or(0) >= questions
+                    })
+                };
+                if categories.is_empty() {
+                    snapshot
+                        .categories
+                        .keys()
+                        .filter(|category| questions(category).is_some())
+                        .all(completed)
+                } else {
+                    categories.iter().all(completed)
+                }
+            }
+            Rule::FastAnswer { seconds } => last.correct && last.seconds <= *seconds,
+        }
+    }
+}
+
+/// Lists every achievement of the event by id, along with the time `user` earned it
+pub async fn achievements_of(
+    pool: &PgPool,
+    snapshot: &BankSnapshot,
+    user: Uuid,
+) -> QuizResult<Vec<AchievementInfo>> {
+    let mut earned = earned(pool, user).await?;
+    let mut achievements = snapshot
+        .achievements()
+        .iter()
+        .flat_map(|achievements| achievements.iter())
+        .map(|(id, achievement)| {
+            AchievementInfo::new(id.clone(), Some(achievement), earned.remove(id))
+        })
+        .collect::<Vec<AchievementInfo>>();
+    // achievements that were earned but removed from the file since are still listed
+    achievements.extend(
+        earned
+            .into_iter()
+            .map(|(id, earned_at)| AchievementInfo::new(id, None, Some(earned_at))),
+    );
+    Ok(achievements)
+}
+
+/// Checks all achievements `user` has not earned yet against their current stats, stores the ones
+/// that were met and returns them
+pub async fn evaluate(
+    pool: &PgPool,
+    snapshot: &BankSnapshot,
+    user: Uuid,
+    last: &AnsweredQuestion,
+) -> QuizResult<Vec<AchievementInfo>> {
+    let achievements = match snapshot.achievements() {
+        Some(achievements) if !achievements.is_empty() => achievements,
+        _ => return Ok(vec![]),
+    };
+    let earned = earned(pool, user).await?;
+    let stats = user_stats(pool, user).await?;
+
+    let mut new = Vec::new();
+    for (id, achievement) in achievements.iter() {
+        if earned.contains_key(id) || !achievement.rule.is_met(&stats, snapshot, last) {
+            continue;
+        }
+        let earned_at: Option<DateTime<Utc>> = sqlx::query_scalar(
+            "INSERT INTO user_achievements VALUES ($1, $2) ON CONFLICT DO NOTHING RETURNING earned_at",
+        )
+        .bind(user)
+        .bind(id)
+        .fetch_optional(pool)
+        .await?;
+        // a concurrent evaluation may have stored it first, it is announced only once
+        if let Some(earned_at) = earned_at {
+            new.push(AchievementInfo::new(
+                id.clone(),
+                Some(achievement),
+                Some(earned_at),
+            ));
+        }
+    }
+    Ok(new)
+}
+
+async fn earned(pool: &PgPool, user: Uuid) -> QuizResult<HashMap<String, DateTime<Utc>>> {
+    Ok(sqlx::query_as::<_, StoredAchievement>(
+        "SELECT achievement, earned_at FROM user_achievements WHERE user_id = $1",
+    )
+    .bind(user)
+    .fetch_all(pool)
+    .await?
+    .into_iter()
+    .map(|stored| (stored.achievement, stored.earned_at))
+    .collect())
+}
+
+async fn user_stats(pool: &PgPool, user: Uuid) -> QuizResult<UserStats> {
+    let scores = category_scores(pool, user).await?;
+    let streak: i64 = sqlx::query_scalar(
+        "SELECT COUNT(*) FROM answers WHERE user_id = $1 AND answered_at > COALESCE( \
+             (SELECT MAX(answered_at) FROM answers WHERE user_id = $1 AND NOT correct), \
+             '-infinity'::timestamptz)",
+    )
+    .bind(user)
+    .fetch_one(pool)
+    .await?;
+    let served: Vec<(String, i64)> = sqlx::query_as(
+        "SELECT category, COUNT(*) FROM question_history WHERE user_id = $1 GROUP BY category",
+    )
+    .bind(user)
+    .fetch_all(pool)
+    .await?;
+    Ok(UserStats {
+        correct: scores
+            .iter()
+            .map(|score| (score.category.clone(), score.correct_answers))
+            .collect(),
+        served: served.into_iter().collect(),
+        points: scores.iter().map(|score| score.points).sum(),
+        streak,
+    })
+}
@@ -0,0 +1,334 @@
+use crate::common::achievements::{validate_achievements, Achievement};
+use crate::common::quest::QuestRoute;
+use crate::common::questions::Question;
+use crate::common::validation::{validate_category, CategoryReport, ValidationError};
+use chrono::{DateTime, Utc};
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::ffi::OsStr;
+use std::path::PathBuf;
+use std::sync::{Arc, RwLock};
+use std::time::{Duration, SystemTime};
+
+#[derive(Debug, Clone)]
+pub struct LoadedCategory {
+    /// Modification time of the file this entry was read from
+    pub modified: SystemTime,
+    /// Last version of the category that passed validation, if there ever was one
+    pub questions: Option<Arc<Vec<Question>>>,
+    /// Validation errors of the file as it currently is on disk
+    pub errors: Vec<ValidationError>,
+}
+
+/// Name of the category manifest file inside the question folder
+pub const MANIFEST_FILE: &str = "categories.toml";
+/// Name of the quest route file inside the question folder
+pub const ROUTE_FILE: &str = "route.toml";
+/// Name of the achievements file inside the question folder
+pub const ACHIEVEMENTS_FILE: &str = "achievements.toml";
+
+/// Display metadata and unlock rules of a category, as declared in the category manifest
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct CategoryMeta {
+    pub title: Option<String>,
+    pub description: Option<String>,
+    pub icon: Option<String>,
+    /// Categories are listed in ascending order
+    pub order: i32,
+    /// Multiplies points awarded for every question in the category
+    pub multiplier: f64,
+    /// All of these have to be fulfilled before the category can be played
+    pub requires: Vec<Requirement>,
+    /// The category can only be played between these two points in time, given as RFC 3339 strings
+    pub opens_at: Option<DateTime<Utc>>,
+    pub closes_at: Option<DateTime<Utc>>,
+}
+
+impl Default for CategoryMeta {
+    fn default() -> Self {
+        CategoryMeta {
+            title: None,
+            description: None,
+            icon: None,
+            order: 0,
+            multiplier: 1.0,
+            requires: vec![],
+            opens_at: None,
+            closes_at: None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Requirement {
+    pub category: String,
+    /// Amount of correct answers required in `category`
+    pub correct: i64,
+}
+
+/// A TOML file from the question folder along with its modification time
+#[derive(Debug)]
+struct ConfigFile<T> {
+    value: Option<Arc<T>>,
+    modified: Option<SystemTime>,
+}
+
+impl<T> Clone for ConfigFile<T> {
+    fn clone(&self) -> Self {
+        ConfigFile {
+            value: self.value.clone(),
+            modified: self.modified,
+        }
+    }
+}
+
+impl<T> Default for ConfigFile<T> {
+    fn default() -> Self {
+        ConfigFile {
+            value: None,
+            modified: None,
+        }
+    }
+}
+
+/// Immutable view of all question categories at some point in time
+#[derive(Debug, Clone, Default)]
+pub struct BankSnapshot {
+    pub categories: BTreeMap<String, LoadedCategory>,
+    manifest: ConfigFile<BTreeMap<String, CategoryMeta>>,
+    route: ConfigFile<QuestRoute>,
+    achievements: ConfigFile<BTreeMap<String, Achievement>>,
+}
+
+impl BankSnapshot {
+    pub fn meta(&self, category: &str) -> CategoryMeta {
+        self.manifest
+            .value
+            .as_ref()
+            .and_then(|manifest| manifest.get(category).cloned())
+            .unwrap_or_default()
+    }
+
+    pub fn route(&self) -> Option<Arc<QuestRoute>> {
+        self.route.value.clone()
+    }
+
+    pub fn achievements(&self) -> Option<Arc<BTreeMap<String, Achievement>>> {
+        self.achievements.value.clone()
+    }
+
+    pub fn reports(&self) -> Vec<CategoryReport> {
+        self.categories
+            .iter()
+            .map(|(category, loaded)| CategoryReport {
+                category: category.clone(),
+                questions: loaded.questions.as_ref().map(|q| q.len()).unwrap_or(0),
+                errors: loaded.errors.clone(),
+            })
+            .collect()
+    }
+}
+
+/// In-memory cache of the question folder.
+///
+/// Categories are parsed once and re-read only when their file changes on disk. A reload builds a
+/// whole new snapshot and swaps it in at once, and a file that fails validation (e.g. because it is
+/// still being written) never replaces the last valid version of its category.
+#[derive(Debug, Clone)]
+pub struct QuestionBank {
+    folder: PathBuf,
+    snapshot: Arc<RwLock<Arc<BankSnapshot>>>,
+}
+
+impl QuestionBank {
+    pub async fn load<P: Into<PathBuf>>(folder: P) -> anyhow::Result<Self> {
+        let bank = Self {
+            folder: folder.into(),
+            snapshot: Arc::new(RwLock::new(Arc::new(BankSnapshot::default()))),
+        };
+        bank.reload().await?;
+        Ok(bank)
+    }
+
+    pub fn snapshot(&self) -> Arc<BankSnapshot> {
+        self.snapshot
+            .read()
+            .expect("Question bank lock poisoned")
+            .clone()
+    }
+
+    /// Re-reads all changed category files, returns whether anything changed
+    pub async fn reload(&self) -> anyhow::Result<bool> {
+        let previous = self.snapshot();
+        let mut next = BankSnapshot::default();
+        let mut changed = false;
+
+        let mut entries = tokio::fs::read_dir(&self.folder).await?;
+        while let Some(entry) = entries.next_entry().await? {
+            let path = entry.path();
+            if path.extension() != Some(OsStr::new("json")) {
+                continue;
+            }
+            let category = match path.file_stem().and_then(|stem| stem.to_str()) {
+                Some(stem) => stem.to_owned(),
+                None => continue,
+            };
+            let modified = entry.metadata().await?.modified()?;
+
+            let old = previous.categories.get(&category);
+            if let Some(old) = old.filter(|old| old.modified == modified) {
+                next.categories.insert(category, old.clone());
+                continue;
+            }
+
+            changed = true;
+            let source = tokio::fs::read_to_string(&path).await?;
+            let loaded = match validate_category(&category, &source) {
+                Ok(questions) => {
+                    log::info!(
+                        "Loaded question category `{category}` with {} questions",
+                        questions.len()
+                    );
+                    LoadedCategory {
+                        modified,
+                        questions: Some(Arc::new(questions)),
+                        errors: vec![],
+                    }
+                }
+                Err(errors) => {
+                    let questions = old.and_then(|old| old.questions.clone());
+                    if questions.is_some() {
+                        log::error!("Question category `{category}` is invalid, still serving its previous version:");
+                    } else {
+                        log::error!(
+                            "Question category `{category}` is invalid and will not be served:"
+                        );
+                    }
+                    for error in &errors {
+                        log::error!("  {error}");
+                    }
+                    LoadedCategory {
+                        modified,
+                        questions,
+                        errors,
+                    }
+                }
+            };
+            next.categories.insert(category, loaded);
+        }
+
+        let (manifest, manifest_changed) = self
+            .reload_config(MANIFEST_FILE, &previous.manifest, |_| vec![])
+            .await?;
+        next.manifest = manifest;
+        changed |= manifest_changed;
+        for (category, meta) in next
+            .manifest
+            .value
+            .iter()
+            .flat_map(|manifest| manifest.iter())
+        {
+            for requirement in &meta.requires {
+                if !next.categories.contains_key(&requirement.category) {
+                    log::warn!(
+                        "Category `{category}` requires unknown category `{}`",
+                        requirement.category
+                    );
+                }
+            }
+        }
+
+        let (route, route_changed) = self
+            .reload_config(ROUTE_FILE, &previous.route, QuestRoute::validate)
+            .await?;
+        next.route = route;
+        changed |= route_changed;
+
+        let (achievements, achievements_changed) = self
+            .reload_config(
+                ACHIEVEMENTS_FILE,
+                &previous.achievements,
+                validate_achievements,
+            )
+            .await?;
+        next.achievements = achievements;
+        changed |= achievements_changed;
+
+        for category in previous.categories.keys() {
+            if !next.categories.contains_key(category) {
+                log::info!("Question category `{category}` was removed");
+                changed = true;
+            }
+        }
+
+        if changed {
+            *self.snapshot.write().expect("Question bank lock poisoned") = Arc::new(next);
+        }
+        Ok(changed)
+    }
+
+    /// Re-reads a TOML file from the question folder if it changed. A file that fails to parse or
+    /// validate does not replace its last valid version.
+    async fn reload_config<T: DeserializeOwned>(
+        &self,
+        file: &str,
+        previous: &ConfigFile<T>,
+        validate: impl Fn(&T) -> Vec<String>,
+    ) -> anyhow::Result<(ConfigFile<T>, bool)> {
+        let path = self.folder.join(file);
+        let modified = match tokio::fs::metadata(&path).await {
+            Ok(metadata) => metadata.modified()?,
+            Err(_) => {
+                if previous.modified.is_some() {
+                    log::info!("`{file}` was removed");
+                }
+                return Ok((ConfigFile::default(), previous.modified.is_some()));
+            }
+        };
+        if previous.modified == Some(modified) {
+            return Ok((previous.clone(), false));
+        }
+
+        let source = tokio::fs::read_to_string(&path).await?;
+        let errors = match toml::from_str::<T>(&source) {
+            Ok(value) => {
+                let errors = validate(&value);
+                if errors.is_empty() {
+                    log::info!("Loaded `{file}`");
+                    let loaded = ConfigFile {
+                        value: Some(Arc::new(value)),
+                        modified: Some(modified),
+                    };
+                    return Ok((loaded, true));
+                }
+                errors
+            }
+            Err(err) => vec![err.to_string()],
+        };
+        log::error!("`{file}` is invalid, still using its previous version:");
+        for error in errors {
+            log::error!("  {error}");
+        }
+        let kept = ConfigFile {
+            value: previous.value.clone(),
+            modified: Some(modified),
+        };
+        Ok((kept, true))
+    }
+
+    /// Spawns a task that polls the question folder for changes every `interval`
+    pub fn watch(&self, interval: Duration) {
+        let bank = self.clone();
+        tokio::spawn(async move {
+            let mut timer = tokio::time::interval(interval);
+            loop {
+                timer.tick().await;
+                if let Err(err) = bank.reload().await {
+                    log::error!("Could not reload question bank: {err}");
+                }
+            }
+        });
+    }
+}
@@ -0,0 +1,65 @@
+use crate::common::achievements::AchievementInfo;
+use crate::common::scores::LeaderboardEntry;
+use serde::Serialize;
+use tokio::sync::broadcast;
+use uuid::Uuid;
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum QuestEvent {
+    UserRegistered {
+        event_id: String,
+        uuid: Uuid,
+        username: String,
+    },
+    QuestionAnswered {
+        event_id: String,
+        uuid: Uuid,
+        question: Uuid,
+        category: String,
+        correct: bool,
+        points: u32,
+    },
+    AchievementEarned {
+        event_id: String,
+        uuid: Uuid,
+        achievement: AchievementInfo,
+    },
+    LeaderboardChanged {
+        event_id: String,
+        top: Vec<LeaderboardEntry>,
+    },
+}
+
+impl QuestEvent {
+    pub fn name(&self) -> &'static str {
+        match self {
+            QuestEvent::UserRegistered { .. } => "user_registered",
+            QuestEvent::QuestionAnswered { .. } => "question_answered",
+            QuestEvent::AchievementEarned { .. } => "achievement_earned",
+            QuestEvent::LeaderboardChanged { .. } => "leaderboard_changed",
+        }
+    }
+}
+
+/// Fan-out channel for live quest events, shared between the HTTP server and the telegram bot
+#[derive(Debug, Clone)]
+pub struct EventBus {
+    sender: broadcast::Sender<QuestEvent>,
+}
+
+impl EventBus {
+    pub fn new(capacity: usize) -> Self {
+        let (sender, _) = broadcast::channel(capacity);
+        Self { sender }
+    }
+
+    pub fn publish(&self, event: QuestEvent) {
+        // an error here only means that nobody is listening right now
+        let _ = self.sender.send(event);
+    }
+
+    pub fn subscribe(&self) -> broadcast::Receiver<QuestEvent> {
+        self.sender.subscribe()
+    }
+}
@@ -0,0 +1,89 @@
+use crate::common::questions::{QuizError, QuizResult};
+use crate::QuizConfig;
+use serde::Serialize;
+use sqlx::{FromRow, PgConnection, PgExecutor};
+use std::fmt::{Display, Formatter};
+use uuid::Uuid;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Lifeline {
+    /// Removes half of the wrong variants of a single answer question
+    FiftyFifty,
+    /// Replaces a question with another one from the same category
+    Skip,
+}
+
+impl Display for Lifeline {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            Lifeline::FiftyFifty => "50/50",
+            Lifeline::Skip => "skip",
+        })
+    }
+}
+
+/// Lifelines a user has left in their event
+#[derive(Debug, Clone, Serialize, FromRow)]
+pub struct Lifelines {
+    pub fifty_fifty: i32,
+    pub skip: i32,
+}
+
+impl Lifelines {
+    fn initial(config: &QuizConfig) -> Self {
+        Self {
+            fifty_fifty: config.fifty_fifty_lifelines as i32,
+            skip: config.skip_lifelines as i32,
+        }
+    }
+}
+
+/// Remaining lifelines of `user`, users that never used one have the configured amount
+pub async fn lifelines_of(
+    executor: impl PgExecutor<'_>,
+    config: &QuizConfig,
+    user: Uuid,
+) -> sqlx::Result<Lifelines> {
+    Ok(
+        sqlx::query_as::<_, Lifelines>(
+            "SELECT fifty_fifty, skip FROM lifelines WHERE user_id = $1",
+        )
+        .bind(user)
+        .fetch_optional(executor)
+        .await?
+        .unwrap_or_else(|| Lifelines::initial(config)),
+    )
+}
+
+/// Takes one `lifeline` from `user` and returns what is left. Meant to run in the transaction
+/// that applies the lifeline, so it is given back if applying it fails.
+pub async fn consume(
+    conn: &mut PgConnection,
+    config: &QuizConfig,
+    user: Uuid,
+    lifeline: Lifeline,
+) -> QuizResult<Lifelines> {
+    let initial = Lifelines::initial(config);
+    sqlx::query("INSERT INTO lifelines VALUES ($1, $2, $3) ON CONFLICT (user_id) DO NOTHING")
+        .bind(user)
+        .bind(initial.fifty_fifty)
+        .bind(initial.skip)
+        .execute(&mut *conn)
+        .await?;
+    let query = match lifeline {
+        Lifeline::FiftyFifty => {
+            "UPDATE lifelines SET fifty_fifty = fifty_fifty - 1 \
+             WHERE user_id = $1 AND fifty_fifty > 0 RETURNING fifty_fifty, skip"
+        }
+        Lifeline::Skip => {
+            "UPDATE lifelines SET skip = skip - 1 \
+             WHERE user_id = $1 AND skip > 0 RETURNING fifty_fifty, skip"
+        }
+    };
+    sqlx::query_as::<_, Lifelines>(query)
+        .bind(user)
+        .fetch_optional(&mut *conn)
+        .await?
+        .ok_or(QuizError::NoLifelinesLeft(lifeline))
+}
@@ -1,2 +1,12 @@
+pub mod achievements;
+pub mod bank;
+pub mod events;
+pub mod lifelines;
 pub mod models;
+pub mod quest;
 pub mod questions;
+pub mod rewards;
+pub mod schedule;
+pub mod scores;
+pub mod teams;
+pub mod validation;
@@ -1,4 +1,7 @@
+use crate::common::questions::Question;
+use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
+use sqlx::types::Json;
 use sqlx::FromRow;
 use uuid::Uuid;
 
@@ -6,6 +9,7 @@ use uuid::Uuid;
 pub struct UserRegStage {
     pub id: Uuid,
     pub hash: String,
+    pub event_id: String,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
@@ -13,4 +17,89 @@ pub struct StoredUser {
     pub card_hash: String,
     pub id: Uuid,
     pub username: String,
+    pub telegram_id: Option<i64>,
+    pub event_id: String,
+}
+
+#[derive(Debug, Clone, FromRow)]
+pub struct StoredQuestionInstance {
+    pub id: Uuid,
+    pub bound_to: Uuid,
+    pub category: String,
+    pub question_key: String,
+    pub question: Json<Question>,
+    pub issued_at: DateTime<Utc>,
+    pub answered_at: Option<DateTime<Utc>>,
+    pub expires_at: Option<DateTime<Utc>>,
+    pub station_id: Option<String>,
+    pub hints_used: i32,
+    pub fifty_fifty_used: bool,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+pub struct StoredStation {
+    pub id: String,
+    pub name: String,
+    pub categories: Vec<String>,
+    pub active_from: Option<DateTime<Utc>>,
+    pub active_until: Option<DateTime<Utc>>,
+}
+
+impl StoredStation {
+    pub fn is_active(&self, at: DateTime<Utc>) -> bool {
+        self.active_from.is_none_or(|from| from <= at)
+            && self.active_until.is_none_or(|until| at < until)
+    }
+}
+
+#[derive(Debug, Clone, FromRow)]
+pub struct StoredProgress {
+    pub user_id: Uuid,
+    pub stage: String,
+    pub stage_started_at: DateTime<Utc>,
+    pub stage_completed_at: Option<DateTime<Utc>>,
+    pub completed_stages: Vec<String>,
+    pub finished_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+pub struct StoredEvent {
+    pub id: String,
+    pub name: String,
+    pub starts_at: DateTime<Utc>,
+    pub ends_at: DateTime<Utc>,
+    /// Folder the question bank of the event is loaded from
+    #[serde(skip)]
+    pub questions: String,
+    /// Points can be exchanged for rewards until then, even after the event ended. `None` keeps
+    /// the reward booth open indefinitely.
+    pub redeem_until: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum EventStatus {
+    NotStarted,
+    Running,
+    Finished,
+}
+
+impl StoredEvent {
+    pub fn is_finished(&self, at: DateTime<Utc>) -> bool {
+        self.ends_at <= at
+    }
+
+    pub fn can_redeem(&self, at: DateTime<Utc>) -> bool {
+        self.redeem_until.is_none_or(|until| at < until)
+    }
+
+    pub fn status(&self, at: DateTime<Utc>) -> EventStatus {
+        if at < self.starts_at {
+            EventStatus::NotStarted
+        } else if self.is_finished(at) {
+            EventStatus::Finished
+        } else {
+            EventStatus::Running
+        }
+    }
 }
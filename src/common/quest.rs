@@ -0,0 +1,203 @@
+use crate::common::bank::QuestionBank;
+use crate::common::models::StoredProgress;
+use crate::common::questions::{QuizError, QuizResult};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::PgPool;
+use std::collections::BTreeMap;
+use uuid::Uuid;
+
+/// Graph of stages a player goes through, declared in the route file of the question folder
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct QuestRoute {
+    /// Stage every player starts at
+    pub start: String,
+    pub stages: BTreeMap<String, Stage>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Stage {
+    pub title: Option<String>,
+    /// Station that has to be visited to complete the stage
+    pub station: String,
+    /// Amount of questions from `station` that have to be answered correctly
+    #[serde(default)]
+    pub correct: i64,
+    /// Stages that unlock once this one is complete. With more than one the player picks a
+    /// branch by visiting its station, with none the quest is finished.
+    #[serde(default)]
+    pub next: Vec<String>,
+}
+
+impl QuestRoute {
+    pub fn validate(&self) -> Vec<String> {
+        let mut errors = Vec::new();
+        if !self.stages.contains_key(&self.start) {
+            errors.push(format!("Start stage `{}` does not exist", self.start));
+        }
+        for (name, stage) in &self.stages {
+            for next in &stage.next {
+                if !self.stages.contains_key(next) {
+                    errors.push(format!("Stage `{name}` leads to unknown stage `{next}`"));
+                }
+            }
+        }
+        errors
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct QuestTarget {
+    pub stage: String,
+    pub title: Option<String>,
+    pub station: String,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct QuestProgress {
+    pub stage: QuestTarget,
+    pub required_correct: i64,
+    pub correct: i64,
+    pub stage_completed: bool,
+    /// Stages that can be started next, empty until the current stage is completed
+    pub next: Vec<QuestTarget>,
+    pub completed_stages: Vec<String>,
+    pub finished_at: Option<DateTime<Utc>>,
+}
+
+/// Tracks the progress of players along the quest route
+#[derive(Debug, Clone)]
+pub struct QuestTracker {
+    pool: PgPool,
+    bank: QuestionBank,
+}
+
+impl QuestTracker {
+    pub fn new(bank: QuestionBank, pool: PgPool) -> Self {
+        Self { pool, bank }
+    }
+
+    /// Moves `user` on to the next stage if their current one is complete and `station` belongs
+    /// to one of the stages it leads to. Any other station on the route is locked for `user`,
+    /// stations that are not part of the route can always be visited. Should be called before the
+    /// visit is recorded.
+    pub async fn advance(&self, user: Uuid, station: &str) -> QuizResult<()> {
+        let on_route = self
+            .bank
+            .snapshot()
+            .route()
+            .is_some_and(|route| route.stages.values().any(|stage| stage.station == station));
+        if !on_route {
+            return Ok(());
+        }
+        // evaluated first, so a stage completed by the last answer already unlocks the next ones
+        let progress = match self.progress(user).await {
+            Ok(Some(progress)) => progress,
+            Ok(None) => return Ok(()),
+            // a broken progress, e.g. a stage removed from the route, must not lock the player out
+            Err(err) => {
+                log::error!("Could not evaluate quest progress of user `{user}`: {err}");
+                return Ok(());
+            }
+        };
+        if progress.stage.station == station {
+            return Ok(());
+        }
+        let next = progress
+            .next
+            .iter()
+            .find(|next| next.station == station)
+            .ok_or_else(|| QuizError::StationLocked(station.to_owned()))?;
+        sqlx::query(
+            "UPDATE quest_progress SET stage = $2, stage_started_at = now(), stage_completed_at = NULL, \
+             completed_stages = array_append(completed_stages, stage) WHERE user_id = $1",
+        )
+        .bind(user)
+        .bind(&next.stage)
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    /// Evaluates and returns the progress of `user`, `None` if there is no quest route
+    pub async fn progress(&self, user: Uuid) -> QuizResult<Option<QuestProgress>> {
+        let route = match self.bank.snapshot().route() {
+            Some(route) => route,
+            None => return Ok(None),
+        };
+        let mut progress = self.load(user, &route).await?;
+        let stage = route.stages.get(&progress.stage).ok_or_else(|| {
+            QuizError::Other(anyhow::Error::msg(format!(
+                "Stage `{}` is no longer part of the quest route",
+                progress.stage
+            )))
+        })?;
+
+        let visited: bool = sqlx::query_scalar(
+            "SELECT EXISTS(SELECT 1 FROM station_visits WHERE user_id = $1 AND station_id = $2 AND visited_at >= $3)",
+        )
+        .bind(user)
+        .bind(&stage.station)
+        .bind(progress.stage_started_at)
+        .fetch_one(&self.pool)
+        .await?;
+        let correct: i64 = sqlx::query_scalar(
+            "SELECT COUNT(*) FROM answers a JOIN question_instances q ON q.id = a.question_id \
+             WHERE a.user_id = $1 AND a.correct AND q.station_id = $2 AND q.issued_at >= $3",
+        )
+        .bind(user)
+        .bind(&stage.station)
+        .bind(progress.stage_started_at)
+        .fetch_one(&self.pool)
+        .await?;
+
+        if progress.stage_completed_at.is_none() && visited && correct >= stage.correct {
+            progress = sqlx::query_as::<_, StoredProgress>(
+                "UPDATE quest_progress SET stage_completed_at = now(), \
+                 finished_at = CASE WHEN $2 THEN now() END WHERE user_id = $1 RETURNING *",
+            )
+            .bind(user)
+            .bind(stage.next.is_empty())
+            .fetch_one(&self.pool)
+            .await?;
+        }
+
+        let target = |name: &String| {
+            route.stages.get(name).map(|stage| QuestTarget {
+                stage: name.clone(),
+                title: stage.title.clone(),
+                station: stage.station.clone(),
+            })
+        };
+        let stage_completed = progress.stage_completed_at.is_some();
+        Ok(Some(QuestProgress {
+            stage: target(&progress.stage).expect("Current stage exists"),
+            required_correct: stage.correct,
+            correct,
+            stage_completed,
+            next: if stage_completed {
+                stage.next.iter().filter_map(target).collect()
+            } else {
+                vec![]
+            },
+            completed_stages: progress.completed_stages,
+            finished_at: progress.finished_at,
+        }))
+    }
+
+    async fn load(&self, user: Uuid, route: &QuestRoute) -> QuizResult<StoredProgress> {
+        sqlx::query(
+            "INSERT INTO quest_progress(user_id, stage) VALUES ($1, $2) ON CONFLICT DO NOTHING",
+        )
+        .bind(user)
+        .bind(&route.start)
+        .execute(&self.pool)
+        .await?;
+        Ok(
+            sqlx::query_as::<_, StoredProgress>("SELECT * FROM quest_progress WHERE user_id = $1")
+                .bind(user)
+                .fetch_one(&self.pool)
+                .await?,
+        )
+    }
+}
@@ -1,103 +1,1083 @@
-use anyhow::bail;
-use rand::prelude::SliceRandom;
+use crate::common::achievements::{achievements_of, evaluate, AchievementInfo, AnsweredQuestion};
+use crate::common::bank::{CategoryMeta, QuestionBank, Requirement};
+use crate::common::lifelines::{consume, Lifeline, Lifelines};
+use crate::common::models::{StoredQuestionInstance, StoredStation};
+use crate::common::scores::category_scores;
+use crate::common::validation::{CategoryReport, ValidationError};
+use crate::QuizConfig;
+use chrono::{DateTime, Utc};
 use rand::rngs::OsRng;
-use serde::{Deserialize, Serialize};
+use rand::seq::SliceRandom;
+use serde::de::Error as _;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use serde_json::{Map, Value};
+use sha2::{Digest, Sha256};
+use sqlx::types::Json;
+use sqlx::{PgConnection, PgExecutor, PgPool};
 use std::collections::HashMap;
-use std::path::PathBuf;
-use tokio::fs::File;
-use tokio::io::AsyncReadExt;
+use std::sync::Arc;
+use std::time::Duration;
+use thiserror::Error;
 use uuid::Uuid;
 
+#[derive(Debug, Error)]
+pub enum QuizError {
+    #[error("Invalid question id `{0}`!")]
+    InvalidQuestion(Uuid),
+    #[error("Question `{question}` was not issued to user `{user}`")]
+    NotBound { question: Uuid, user: Uuid },
+    #[error("Question category `{0}` does not exist!")]
+    UnknownCategory(String),
+    #[error("Question category `{category}` failed validation with {} errors", errors.len())]
+    InvalidCategory {
+        category: String,
+        errors: Vec<ValidationError>,
+    },
+    #[error("Question category `{0}` is locked")]
+    CategoryLocked(String),
+    #[error("All questions from category `{0}` were already served")]
+    CategoryComplete(String),
+    #[error("All questions from station `{0}` were already served")]
+    StationComplete(String),
+    #[error("Station `{0}` is not unlocked on the quest route yet")]
+    StationLocked(String),
+    #[error("Time to answer question `{0}` has run out")]
+    Expired(Uuid),
+    #[error("All hints of question `{0}` were already revealed")]
+    NoHintsLeft(Uuid),
+    #[error("There are no {0} lifelines left")]
+    NoLifelinesLeft(Lifeline),
+    #[error("The {lifeline} lifeline can not be used on question `{question}`")]
+    LifelineNotApplicable { lifeline: Lifeline, question: Uuid },
+    #[error("Expected a `{expected}` answer, but got a `{got}` answer")]
+    AnswerTypeMismatch {
+        expected: &'static str,
+        got: &'static str,
+    },
+    #[error("User `{0}` does not exist")]
+    UnknownUser(Uuid),
+    #[error("Event `{0}` does not exist")]
+    UnknownEvent(String),
+    #[error("There is no active event")]
+    NoActiveEvent,
+    #[error("Event `{event}` starts at {starts_at}")]
+    EventNotStarted {
+        event: String,
+        starts_at: DateTime<Utc>,
+    },
+    #[error("Event `{0}` is over and can not be played anymore")]
+    EventFinished(String),
+    #[error("Question category `{category}` opens at {opens_at}")]
+    CategoryNotOpen {
+        category: String,
+        opens_at: DateTime<Utc>,
+    },
+    #[error("Question category `{0}` is closed")]
+    CategoryClosed(String),
+    #[error(transparent)]
+    Sql(#[from] sqlx::Error),
+    #[error(transparent)]
+    Other(#[from] anyhow::Error),
+}
+
+pub type QuizResult<T> = Result<T, QuizError>;
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct SingleAnswerQuestion {
+pub struct Question {
+    /// Identifies the question in the history of served questions. Questions without one are
+    /// identified by their text, so rewording them makes them a new question.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub id: Option<String>,
     pub question: String,
+    #[serde(default)]
+    pub points: Option<u32>,
+    /// Seconds the player has to answer, overrides the category default
+    #[serde(default)]
+    pub time_limit: Option<u64>,
+    /// Revealed one by one on request, each one lowers the points for a correct answer
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub hints: Vec<String>,
+    /// Shown to the user once the question is answered
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub explanation: Option<String>,
+    /// Whether variants are shuffled for every issued instance
+    #[serde(default = "default_shuffle")]
+    pub shuffle: bool,
+    #[serde(flatten)]
+    pub kind: QuestionKind,
+}
+
+fn default_shuffle() -> bool {
+    true
+}
+
+impl Question {
+    /// Stable key of the question that survives reordering the category file, the `id` if there
+    /// is one or a hash of the question text otherwise
+    pub fn key(&self) -> String {
+        self.id.clone().unwrap_or_else(|| {
+            let text = self
+                .question
+                .split_whitespace()
+                .collect::<Vec<&str>>()
+                .join(" ");
+            format!("{:x}", Sha256::digest(text.as_bytes()))
+        })
+    }
+}
+
+// `remote = "Self"` turns the derived impls into inherent functions, so `Deserialize` can default
+// the tag below
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case", remote = "Self")]
+pub enum QuestionKind {
+    Single(SingleAnswerQuestion),
+    Multiple(MultipleAnswerQuestion),
+    Text(TextAnswerQuestion),
+    Numeric(NumericAnswerQuestion),
+    Ordering(OrderingQuestion),
+}
+
+impl Serialize for QuestionKind {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        QuestionKind::serialize(self, serializer)
+    }
+}
+
+/// Questions without a `type` are single answer questions, like all questions were before the
+/// other types existed
+impl<'de> Deserialize<'de> for QuestionKind {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let mut fields = Map::deserialize(deserializer)?;
+        fields
+            .entry("type")
+            .or_insert_with(|| Value::from("single"));
+        QuestionKind::deserialize(Value::Object(fields)).map_err(D::Error::custom)
+    }
+}
+
+/// Exactly one of `variants` is correct
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SingleAnswerQuestion {
     pub variants: Vec<String>,
     pub correct_answer: u8,
+    /// Shown after answering with the variant at the same index
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub feedback: Vec<Option<String>>,
+}
+
+/// Any subset of `variants` may be correct, the answer has to match it exactly
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MultipleAnswerQuestion {
+    pub variants: Vec<String>,
+    pub correct_answers: Vec<u8>,
+    /// Shown after picking the variant at the same index
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub feedback: Vec<Option<String>>,
+}
+
+/// Free-form answer, matched against `accepted` ignoring case and extra whitespace
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TextAnswerQuestion {
+    pub accepted: Vec<String>,
+}
+
+/// Numeric answer, accepted if it is within `tolerance` of `answer`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct NumericAnswerQuestion {
+    pub answer: f64,
+    #[serde(default)]
+    pub tolerance: f64,
+}
+
+/// `items` have to be put into `correct_order`, which lists indices of `items`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct OrderingQuestion {
+    pub items: Vec<String>,
+    pub correct_order: Vec<u8>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", content = "answer", rename_all = "snake_case")]
+pub enum Answer {
+    Single(u8),
+    Multiple(Vec<u8>),
+    Text(String),
+    Numeric(f64),
+    Ordering(Vec<u8>),
+}
+
+impl Answer {
+    pub fn kind(&self) -> &'static str {
+        match self {
+            Answer::Single(_) => "single",
+            Answer::Multiple(_) => "multiple",
+            Answer::Text(_) => "text",
+            Answer::Numeric(_) => "numeric",
+            Answer::Ordering(_) => "ordering",
+        }
+    }
+}
+
+impl QuestionKind {
+    pub fn correct_answer(&self) -> Answer {
+        match self {
+            QuestionKind::Single(q) => Answer::Single(q.correct_answer),
+            QuestionKind::Multiple(q) => Answer::Multiple(q.correct_answers.clone()),
+            QuestionKind::Text(q) => Answer::Text(q.accepted.first().cloned().unwrap_or_default()),
+            QuestionKind::Numeric(q) => Answer::Numeric(q.answer),
+            QuestionKind::Ordering(q) => Answer::Ordering(q.correct_order.clone()),
+        }
+    }
+
+    /// Puts variants and items into a random order, remapping the correct answer to it
+    pub fn shuffle(&mut self) {
+        match self {
+            QuestionKind::Single(q) => {
+                let (order, positions) = random_order(q.variants.len());
+                reorder(&mut q.variants, &order);
+                reorder(&mut q.feedback, &order);
+                q.correct_answer = positions[q.correct_answer as usize];
+            }
+            QuestionKind::Multiple(q) => {
+                let (order, positions) = random_order(q.variants.len());
+                reorder(&mut q.variants, &order);
+                reorder(&mut q.feedback, &order);
+                for answer in &mut q.correct_answers {
+                    *answer = positions[*answer as usize];
+                }
+            }
+            QuestionKind::Ordering(q) => {
+                let (order, positions) = random_order(q.items.len());
+                reorder(&mut q.items, &order);
+                for index in &mut q.correct_order {
+                    *index = positions[*index as usize];
+                }
+            }
+            QuestionKind::Text(_) | QuestionKind::Numeric(_) => {}
+        }
+    }
+
+    /// Feedback written for the variants picked in `answer`
+    pub fn feedback_for(&self, answer: &Answer) -> Vec<String> {
+        let (feedback, picked) = match (self, answer) {
+            (QuestionKind::Single(q), Answer::Single(answer)) => {
+                (&q.feedback, std::slice::from_ref(answer))
+            }
+            (QuestionKind::Multiple(q), Answer::Multiple(answers)) => {
+                (&q.feedback, answers.as_slice())
+            }
+            _ => return Vec::new(),
+        };
+        let mut picked = picked.to_vec();
+        picked.sort_unstable();
+        picked.dedup();
+        picked
+            .into_iter()
+            .filter_map(|index| feedback.get(index as usize).cloned().flatten())
+            .collect()
+    }
+
+    pub fn check(&self, answer: &Answer) -> QuizResult<bool> {
+        Ok(match (self, answer) {
+            (QuestionKind::Single(q), Answer::Single(answer)) => q.correct_answer == *answer,
+            (QuestionKind::Multiple(q), Answer::Multiple(answers)) => {
+                let mut expected = q.correct_answers.clone();
+                let mut answers = answers.clone();
+                expected.sort_unstable();
+                expected.dedup();
+                answers.sort_unstable();
+                answers.dedup();
+                expected == answers
+            }
+            (QuestionKind::Text(q), Answer::Text(answer)) => {
+                let answer = normalize_text(answer);
+                q.accepted
+                    .iter()
+                    .any(|accepted| normalize_text(accepted) == answer)
+            }
+            (QuestionKind::Numeric(q), Answer::Numeric(answer)) => {
+                (q.answer - answer).abs() <= q.tolerance
+            }
+            (QuestionKind::Ordering(q), Answer::Ordering(order)) => q.correct_order == *order,
+            (_, answer) => {
+                return Err(QuizError::AnswerTypeMismatch {
+                    expected: self.correct_answer().kind(),
+                    got: answer.kind(),
+                })
+            }
+        })
+    }
+}
+
+/// Random order of `len` items, along with the new position of every item indexed by its old
+/// position
+fn random_order(len: usize) -> (Vec<usize>, Vec<u8>) {
+    let mut order = (0..len).collect::<Vec<usize>>();
+    order.shuffle(&mut OsRng);
+    let mut positions = vec![0; len];
+    for (position, index) in order.iter().enumerate() {
+        positions[*index] = position as u8;
+    }
+    (order, positions)
+}
+
+/// Puts `items` into `order`, leaves them alone if they are not one per position
+fn reorder<T: Clone>(items: &mut Vec<T>, order: &[usize]) {
+    if items.len() == order.len() {
+        *items = order.iter().map(|index| items[*index].clone()).collect();
+    }
+}
+
+fn is_unlocked(meta: &CategoryMeta, correct: &HashMap<String, i64>) -> bool {
+    meta.requires.iter().all(|requirement| {
+        correct.get(&requirement.category).copied().unwrap_or(0) >= requirement.correct
+    })
+}
+
+/// Amount of correct answers of `user` in each category
+async fn correct_answers(
+    executor: impl PgExecutor<'_>,
+    user: Uuid,
+) -> QuizResult<HashMap<String, i64>> {
+    Ok(category_scores(executor, user)
+        .await?
+        .into_iter()
+        .map(|score| (score.category, score.correct_answers))
+        .collect())
+}
+
+/// Locks an unanswered question for the rest of the transaction, making sure it is still valid
+/// and was issued to `user`, if one is given
+async fn lock_unanswered(
+    conn: &mut PgConnection,
+    user: Option<Uuid>,
+    question_id: Uuid,
+) -> QuizResult<StoredQuestionInstance> {
+    let instance = sqlx::query_as::<_, StoredQuestionInstance>(
+        "SELECT * FROM question_instances WHERE id = $1 AND answered_at IS NULL FOR UPDATE",
+    )
+    .bind(question_id)
+    .fetch_optional(conn)
+    .await?
+    .ok_or(QuizError::InvalidQuestion(question_id))?;
+    if let Some(user) = user.filter(|user| *user != instance.bound_to) {
+        return Err(QuizError::NotBound {
+            question: question_id,
+            user,
+        });
+    }
+    if matches!(instance.expires_at, Some(expires_at) if expires_at < Utc::now()) {
+        return Err(QuizError::Expired(question_id));
+    }
+    Ok(instance)
+}
+
+fn normalize_text(text: &str) -> String {
+    text.split_whitespace()
+        .collect::<Vec<&str>>()
+        .join(" ")
+        .to_lowercase()
+}
+
+/// Issued question with everything needed to check the answer, never sent to clients as is.
+/// See [IssuedQuestion](crate::server::models::IssuedQuestion) for what the users get to see.
+#[derive(Debug, Clone)]
 pub struct QuestionInstance {
     pub id: Uuid,
     pub bound_to: Uuid,
-    #[serde(flatten)]
-    pub question: SingleAnswerQuestion,
+    pub category: String,
+    pub issued_at: DateTime<Utc>,
+    pub expires_at: Option<DateTime<Utc>>,
+    pub station: Option<String>,
+    pub hints_used: i32,
+    pub question: Question,
+}
+
+impl From<StoredQuestionInstance> for QuestionInstance {
+    fn from(stored: StoredQuestionInstance) -> Self {
+        Self {
+            id: stored.id,
+            bound_to: stored.bound_to,
+            category: stored.category,
+            issued_at: stored.issued_at,
+            expires_at: stored.expires_at,
+            station: stored.station_id,
+            hints_used: stored.hints_used,
+            question: stored.question.0,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Hint {
+    pub hint: String,
+    /// Hints of the question revealed so far, including this one
+    pub used: i32,
+    pub remaining: usize,
+    /// Share of the points that will be deducted from a correct answer
+    pub penalty: f64,
 }
 
+#[derive(Debug, Clone)]
+pub struct AnswerResult {
+    pub category: String,
+    pub station: Option<String>,
+    pub correct: bool,
+    pub correct_answer: Answer,
+    pub points: u32,
+    /// Consecutive correct answers of the user in the category, including this one
+    pub streak: i32,
+    /// Points of the user after this answer
+    pub total: i64,
+    pub explanation: Option<String>,
+    /// Feedback on the picked variants
+    pub feedback: Vec<String>,
+    /// Achievements earned with this answer
+    pub achievements: Vec<AchievementInfo>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct CategoryInfo {
+    pub name: String,
+    pub title: String,
+    pub description: Option<String>,
+    pub icon: Option<String>,
+    pub order: i32,
+    pub multiplier: f64,
+    pub opens_at: Option<DateTime<Utc>>,
+    pub closes_at: Option<DateTime<Utc>>,
+    pub questions: usize,
+    pub requires: Vec<Requirement>,
+    /// Whether the requesting user can play this category, `None` if no user was specified
+    pub unlocked: Option<bool>,
+}
+
+/// Entry point of the quiz engine. Cheap to clone and safe to share between concurrent requests:
+/// the question bank is read through immutable snapshots, and issued instances live in Postgres,
+/// where row locks serialize answers to the same question.
 #[derive(Debug, Clone)]
 pub struct QuizHandler {
-    instances: HashMap<Uuid, QuestionInstance>,
-    question_folder: PathBuf,
+    pool: PgPool,
+    bank: QuestionBank,
+    config: Arc<QuizConfig>,
+    /// Event answers are recorded in
+    event: String,
 }
 
 impl QuizHandler {
-    pub fn new<P: Into<PathBuf>>(question_folder: P) -> Self {
+    pub fn new(bank: QuestionBank, pool: PgPool, config: Arc<QuizConfig>, event: String) -> Self {
         Self {
-            instances: HashMap::new(),
-            question_folder: question_folder.into(),
+            pool,
+            bank,
+            config,
+            event,
         }
     }
 
-    pub async fn get_all_categories(&mut self) -> anyhow::Result<Vec<String>> {
-        Ok(std::fs::read_dir(&self.question_folder)?
-            .map(|entry| {
-                entry
-                    .expect("Invalid entry")
-                    .file_name()
-                    .into_string()
-                    .expect("Invalid OS String")
+    /// Points awarded for correctly answering `question` from `category`
+    pub fn points_for(&self, category: &str, question: &Question) -> u32 {
+        let base = question.points.unwrap_or_else(|| {
+            self.config
+                .category_points
+                .get(category)
+                .copied()
+                .unwrap_or(self.config.default_points)
+        });
+        let multiplier = self.bank.snapshot().meta(category).multiplier;
+        (base as f64 * multiplier).round() as u32
+    }
+
+    /// Multiplier for a correct answer that follows `streak` correct answers in the same category
+    pub fn streak_multiplier(&self, streak: i32) -> f64 {
+        (1.0 + self.config.streak_step * streak.max(0) as f64)
+            .min(self.config.max_streak_multiplier)
+            .max(1.0)
+    }
+
+    /// Multiplier for a correct answer given `elapsed` seconds after the question was issued
+    pub fn time_multiplier(&self, elapsed: f64) -> f64 {
+        if self.config.time_bonus_window == 0 {
+            return 1.0;
+        }
+        let remaining = 1.0 - elapsed.max(0.0) / self.config.time_bonus_window as f64;
+        1.0 + self.config.time_bonus * remaining.max(0.0)
+    }
+
+    /// Share of the points kept after `used` hints were revealed
+    pub fn hint_multiplier(&self, used: i32) -> f64 {
+        (1.0 - self.config.hint_penalty * used.max(0) as f64).max(0.0)
+    }
+
+    /// Seconds given to answer `question` from `category`, `None` if there is no limit
+    pub fn time_limit_for(&self, category: &str, question: &Question) -> Option<u64> {
+        question.time_limit.or_else(|| {
+            self.config
+                .category_time_limits
+                .get(category)
+                .copied()
+                .or(self.config.default_time_limit)
+        })
+    }
+
+    pub async fn get_all_from_category(&self, category: String) -> QuizResult<Arc<Vec<Question>>> {
+        let snapshot = self.bank.snapshot();
+        let loaded = snapshot
+            .categories
+            .get(&category)
+            .ok_or_else(|| QuizError::UnknownCategory(category.clone()))?;
+        loaded
+            .questions
+            .clone()
+            .ok_or_else(|| QuizError::InvalidCategory {
+                category,
+                errors: loaded.errors.clone(),
+            })
+    }
+
+    /// Lists all playable categories with their metadata, in display order
+    pub async fn categories(&self, user: Option<Uuid>) -> QuizResult<Vec<CategoryInfo>> {
+        let correct = match user {
+            Some(user) => Some(correct_answers(&self.pool, user).await?),
+            None => None,
+        };
+        let snapshot = self.bank.snapshot();
+        let mut categories = snapshot
+            .categories
+            .iter()
+            .filter_map(|(name, loaded)| {
+                let questions = loaded.questions.as_ref()?.len();
+                let meta = snapshot.meta(name);
+                Some(CategoryInfo {
+                    name: name.clone(),
+                    title: meta.title.clone().unwrap_or_else(|| name.clone()),
+                    description: meta.description.clone(),
+                    icon: meta.icon.clone(),
+                    order: meta.order,
+                    multiplier: meta.multiplier,
+                    opens_at: meta.opens_at,
+                    closes_at: meta.closes_at,
+                    questions,
+                    unlocked: correct.as_ref().map(|correct| is_unlocked(&meta, correct)),
+                    requires: meta.requires,
+                })
             })
-            .collect::<Vec<String>>())
+            .collect::<Vec<CategoryInfo>>();
+        categories.sort_by(|a, b| a.order.cmp(&b.order).then_with(|| a.name.cmp(&b.name)));
+        Ok(categories)
+    }
+
+    pub async fn achievements(&self, user: Uuid) -> QuizResult<Vec<AchievementInfo>> {
+        achievements_of(&self.pool, &self.bank.snapshot(), user).await
+    }
+
+    /// Re-reads the question folder and reports the state of every category
+    pub async fn validate_all(&self) -> anyhow::Result<Vec<CategoryReport>> {
+        self.bank.reload().await?;
+        Ok(self.bank.snapshot().reports())
     }
 
-    pub async fn get_all_from_category(
-        &mut self,
+    pub async fn get_from_category(
+        &self,
+        user: Uuid,
         category: String,
-    ) -> anyhow::Result<Vec<SingleAnswerQuestion>> {
-        let path = self
-            .question_folder
-            .clone()
-            .join(format!("{category}.json"));
-        if !path.exists() {
-            bail!("Question category {category} does not exist!")
+    ) -> QuizResult<QuestionInstance> {
+        self.issue(user, category, None).await
+    }
+
+    /// Issues a question from the first category of `station` that still has one for `user`
+    pub async fn get_from_station(
+        &self,
+        user: Uuid,
+        station: &StoredStation,
+    ) -> QuizResult<QuestionInstance> {
+        let mut result = Err(QuizError::StationComplete(station.id.clone()));
+        for category in &station.categories {
+            match self.issue(user, category.clone(), Some(&station.id)).await {
+                Err(QuizError::CategoryComplete(_)) => continue,
+                Err(
+                    err @ (QuizError::CategoryLocked(_)
+                    | QuizError::CategoryNotOpen { .. }
+                    | QuizError::CategoryClosed(_)),
+                ) => result = Err(err),
+                other => return other,
+            }
         }
-        let mut file = File::open(path).await?;
-        let mut buf = String::new();
-        let _ = file.read_to_string(&mut buf).await?;
-        drop(file);
-        serde_json::from_str(&buf).map_err(anyhow::Error::from)
+        result
     }
 
-    pub async fn get_from_category(
-        &mut self,
+    async fn issue(
+        &self,
+        user: Uuid,
+        category: String,
+        station: Option<&str>,
+    ) -> QuizResult<QuestionInstance> {
+        let mut tx = self.pool.begin().await?;
+        let issued = self.issue_in(&mut tx, user, category, station).await?;
+        tx.commit().await?;
+        Ok(issued)
+    }
+
+    /// Issues a question within the transaction of `conn`, the claim in the question history only
+    /// sticks if it is committed
+    async fn issue_in(
+        &self,
+        conn: &mut PgConnection,
         user: Uuid,
         category: String,
-    ) -> anyhow::Result<QuestionInstance> {
-        let question = self
-            .get_all_from_category(category.clone())
+        station: Option<&str>,
+    ) -> QuizResult<QuestionInstance> {
+        let questions = self.get_all_from_category(category.clone()).await?;
+        let meta = self.bank.snapshot().meta(&category);
+        let now = Utc::now();
+        if let Some(opens_at) = meta.opens_at.filter(|opens_at| now < *opens_at) {
+            return Err(QuizError::CategoryNotOpen { category, opens_at });
+        }
+        if meta.closes_at.is_some_and(|closes_at| closes_at <= now) {
+            return Err(QuizError::CategoryClosed(category));
+        }
+        if !meta.requires.is_empty()
+            && !is_unlocked(&meta, &correct_answers(&mut *conn, user).await?)
+        {
+            return Err(QuizError::CategoryLocked(category));
+        }
+
+        let served: Vec<String> = sqlx::query_scalar(
+            "SELECT question_key FROM question_history WHERE user_id = $1 AND category = $2",
+        )
+        .bind(user)
+        .bind(&category)
+        .fetch_all(&mut *conn)
+        .await?;
+        let keys = questions.iter().map(Question::key).collect::<Vec<String>>();
+        let mut unseen = (0..questions.len())
+            .filter(|index| !served.contains(&keys[*index]))
+            .collect::<Vec<usize>>();
+
+        // A concurrent request of the same user may claim the picked question first, in which case
+        // another one is tried
+        let index = loop {
+            let index = *unseen
+                .choose(&mut OsRng)
+                .ok_or_else(|| QuizError::CategoryComplete(category.clone()))?;
+            let claimed = sqlx::query(
+                "INSERT INTO question_history VALUES ($1, $2, $3) ON CONFLICT DO NOTHING",
+            )
+            .bind(user)
+            .bind(&category)
+            .bind(&keys[index])
+            .execute(&mut *conn)
             .await?
-            .choose(&mut OsRng)
-            .ok_or(anyhow::Error::msg(format!(
-                "No elements in category {category}!"
-            )))?
+            .rows_affected()
+                == 1;
+            if claimed {
+                break index;
+            }
+            unseen.retain(|unseen| *unseen != index);
+        };
+        let mut question = questions[index].clone();
+        if question.shuffle {
+            question.kind.shuffle();
+        }
+        let stored = sqlx::query_as::<_, StoredQuestionInstance>(
+            "INSERT INTO question_instances(id, bound_to, category, question_key, question, expires_at, station_id) \
+             VALUES ($1, $2, $3, $4, $5, now() + $6 * interval '1 second', $7) RETURNING *",
+        )
+        .bind(Uuid::new_v4())
+        .bind(user)
+        .bind(&category)
+        .bind(&keys[index])
+        .bind(Json(&question))
+        .bind(
+            self.time_limit_for(&category, &question)
+                .map(|limit| limit as f64),
+        )
+        .bind(station)
+        .fetch_one(&mut *conn)
+        .await?;
+        Ok(stored.into())
+    }
+
+    /// Removes half of the wrong variants of a single answer question, rounded up, but always
+    /// keeps one of them. The instance keeps the reduced set, so answers refer to the returned
+    /// variants.
+    pub async fn fifty_fifty(
+        &self,
+        user: Uuid,
+        question_id: Uuid,
+    ) -> QuizResult<(QuestionInstance, Lifelines)> {
+        let mut tx = self.pool.begin().await?;
+        let mut instance = lock_unanswered(&mut tx, Some(user), question_id).await?;
+        let not_applicable = QuizError::LifelineNotApplicable {
+            lifeline: Lifeline::FiftyFifty,
+            question: question_id,
+        };
+        if instance.fifty_fifty_used {
+            return Err(not_applicable);
+        }
+        let QuestionKind::Single(question) = &mut instance.question.kind else {
+            return Err(not_applicable);
+        };
+        let correct = question.correct_answer as usize;
+        let wrong = (0..question.variants.len())
+            .filter(|index| *index != correct)
+            .collect::<Vec<usize>>();
+        if wrong.len() < 2 {
+            return Err(not_applicable);
+        }
+        let removed = wrong
+            .choose_multiple(&mut OsRng, wrong.len().div_ceil(2))
+            .copied()
+            .collect::<Vec<usize>>();
+        question.correct_answer =
+            (correct - removed.iter().filter(|index| **index < correct).count()) as u8;
+        question.variants = std::mem::take(&mut question.variants)
+            .into_iter()
+            .enumerate()
+            .filter(|(index, _)| !removed.contains(index))
+            .map(|(_, variant)| variant)
+            .collect();
+        question.feedback = std::mem::take(&mut question.feedback)
+            .into_iter()
+            .enumerate()
+            .filter(|(index, _)| !removed.contains(index))
+            .map(|(_, feedback)| feedback)
+            .collect();
+
+        let lifelines = consume(&mut tx, &self.config, user, Lifeline::FiftyFifty).await?;
+        let stored = sqlx::query_as::<_, StoredQuestionInstance>(
+            "UPDATE question_instances SET question = $2, fifty_fifty_used = true WHERE id = $1 RETURNING *",
+        )
+        .bind(question_id)
+        .bind(&instance.question)
+        .fetch_one(&mut tx)
+        .await?;
+        tx.commit().await?;
+        Ok((stored.into(), lifelines))
+    }
+
+    /// Replaces a question with a new one from the same category. The skipped question is not
+    /// counted as answered and will not be issued again.
+    pub async fn skip(
+        &self,
+        user: Uuid,
+        question_id: Uuid,
+    ) -> QuizResult<(QuestionInstance, Lifelines)> {
+        let mut tx = self.pool.begin().await?;
+        let instance = lock_unanswered(&mut tx, Some(user), question_id).await?;
+        let lifelines = consume(&mut tx, &self.config, user, Lifeline::Skip).await?;
+        // Issued in the same transaction, so the lifeline is kept if there is nothing to replace
+        // the question with
+        let issued = self
+            .issue_in(
+                &mut tx,
+                user,
+                instance.category,
+                instance.station_id.as_deref(),
+            )
+            .await?;
+        sqlx::query("DELETE FROM question_instances WHERE id = $1")
+            .bind(question_id)
+            .execute(&mut tx)
+            .await?;
+        tx.commit().await?;
+        Ok((issued, lifelines))
+    }
+
+    /// Reveals the next hint of an unanswered question issued to `user`
+    pub async fn hint(&self, user: Uuid, question_id: Uuid) -> QuizResult<Hint> {
+        let mut tx = self.pool.begin().await?;
+        let instance = lock_unanswered(&mut tx, Some(user), question_id).await?;
+        let hints = &instance.question.hints;
+        let hint = hints
+            .get(instance.hints_used.max(0) as usize)
+            .ok_or(QuizError::NoHintsLeft(question_id))?
             .clone();
-        let id = Uuid::new_v4();
-        let instance = QuestionInstance {
-            id,
-            bound_to: user,
-            question,
+        let used: i32 = sqlx::query_scalar(
+            "UPDATE question_instances SET hints_used = hints_used + 1 WHERE id = $1 RETURNING hints_used",
+        )
+        .bind(question_id)
+        .fetch_one(&mut tx)
+        .await?;
+        tx.commit().await?;
+        Ok(Hint {
+            hint,
+            used,
+            remaining: hints.len().saturating_sub(used as usize),
+            penalty: 1.0 - self.hint_multiplier(used),
+        })
+    }
+
+    pub async fn answer(
+        &self,
+        user: Uuid,
+        question_id: Uuid,
+        answer: Answer,
+    ) -> QuizResult<AnswerResult> {
+        let mut tx = self.pool.begin().await?;
+        let instance = lock_unanswered(&mut tx, Some(user), question_id).await?;
+        let correct = instance.question.kind.check(&answer)?;
+        let elapsed = (Utc::now() - instance.issued_at).num_milliseconds() as f64 / 1000.0;
+
+        let streak: i32 = sqlx::query_scalar(
+            "INSERT INTO streaks VALUES ($1, $2, $3, $3) ON CONFLICT (user_id, category) DO UPDATE \
+             SET current = CASE WHEN $4 THEN streaks.current + 1 ELSE 0 END, \
+             best = GREATEST(streaks.best, CASE WHEN $4 THEN streaks.current + 1 ELSE 0 END) \
+             RETURNING current",
+        )
+        .bind(user)
+        .bind(&instance.category)
+        .bind(correct as i32)
+        .bind(correct)
+        .fetch_one(&mut tx)
+        .await?;
+        let points = if correct {
+            let base = self.points_for(&instance.category, &instance.question) as f64;
+            (base
+                * self.streak_multiplier(streak - 1)
+                * self.time_multiplier(elapsed)
+                * self.hint_multiplier(instance.hints_used))
+            .round() as u32
+        } else {
+            0
+        };
+
+        sqlx::query("UPDATE question_instances SET answered_at = now() WHERE id = $1")
+            .bind(question_id)
+            .execute(&mut tx)
+            .await?;
+        sqlx::query(
+            "INSERT INTO answers(question_id, user_id, category, question_key, correct, points, event_id) \
+             VALUES ($1, $2, $3, $4, $5, $6, $7)",
+        )
+        .bind(question_id)
+        .bind(user)
+        .bind(&instance.category)
+        .bind(&instance.question_key)
+        .bind(correct)
+        .bind(points as i32)
+        .bind(&self.event)
+        .execute(&mut tx)
+        .await?;
+        let total: i64 = sqlx::query_scalar(
+            "SELECT COALESCE(SUM(points), 0)::bigint FROM answers WHERE user_id = $1",
+        )
+        .bind(user)
+        .fetch_one(&mut tx)
+        .await?;
+        tx.commit().await?;
+
+        let answered = AnsweredQuestion {
+            correct,
+            seconds: elapsed,
         };
-        self.instances.insert(id, instance.clone());
-        Ok(instance)
-    }
-
-    pub async fn answer(&mut self, question_id: Uuid, answer: u8) -> anyhow::Result<(bool, u8)> {
-        let instance = self
-            .instances
-            .remove(&question_id)
-            .ok_or(anyhow::Error::msg("Invalid question id!"))?;
-        return Ok((
-            instance.question.correct_answer == answer,
-            instance.question.correct_answer,
+        // the answer is already recorded at this point, so a failure here must not fail it
+        let achievements = evaluate(&self.pool, &self.bank.snapshot(), user, &answered)
+            .await
+            .unwrap_or_else(|err| {
+                log::error!("Could not evaluate achievements of user `{user}`: {err}");
+                vec![]
+            });
+
+        Ok(AnswerResult {
+            category: instance.category,
+            station: instance.station_id,
+            correct,
+            correct_answer: instance.question.kind.correct_answer(),
+            points,
+            streak,
+            total,
+            feedback: instance.question.kind.feedback_for(&answer),
+            explanation: instance.question.0.explanation,
+            achievements,
+        })
+    }
+
+    /// Deletes unanswered instances of all events whose time to answer ran out more than `grace`
+    /// ago, returns how many were deleted
+    pub async fn purge_expired(pool: &PgPool, grace: Duration) -> QuizResult<u64> {
+        let deleted = sqlx::query(
+            "DELETE FROM question_instances \
+             WHERE answered_at IS NULL AND expires_at < now() - $1 * interval '1 second'",
+        )
+        .bind(grace.as_secs_f64())
+        .execute(pool)
+        .await?;
+        Ok(deleted.rows_affected())
+    }
+
+    /// Spawns a task that purges instances expired for longer than `grace` every `interval`
+    pub fn collect_garbage(pool: PgPool, interval: Duration, grace: Duration) {
+        tokio::spawn(async move {
+            let mut timer = tokio::time::interval(interval);
+            loop {
+                timer.tick().await;
+                match Self::purge_expired(&pool, grace).await {
+                    Ok(0) => {}
+                    Ok(purged) => log::debug!("Purged {purged} expired question instances"),
+                    Err(err) => log::error!("Could not purge expired question instances: {err}"),
+                }
+            }
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn variants(count: usize) -> Vec<String> {
+        (0..count).map(|index| format!("variant {index}")).collect()
+    }
+
+    /// Feedback that names its variant, so alignment can be checked after shuffling
+    fn feedback(variants: &[String]) -> Vec<Option<String>> {
+        variants
+            .iter()
+            .map(|variant| Some(format!("about {variant}")))
+            .collect()
+    }
+
+    fn assert_feedback_aligned(variants: &[String], feedback: &[Option<String>]) {
+        assert_eq!(variants.len(), feedback.len());
+        for (variant, feedback) in variants.iter().zip(feedback) {
+            assert_eq!(
+                feedback.as_deref(),
+                Some(format!("about {variant}").as_str())
+            );
+        }
+    }
+
+    #[test]
+    fn shuffled_single_keeps_correct_answer_and_feedback() {
+        for count in 1..=6 {
+            for correct in 0..count {
+                let variants = variants(count);
+                let expected = variants[correct].clone();
+                let mut kind = QuestionKind::Single(SingleAnswerQuestion {
+                    feedback: feedback(&variants),
+                    variants,
+                    correct_answer: correct as u8,
+                });
+                kind.shuffle();
+                assert!(kind.check(&kind.correct_answer()).unwrap());
+                let QuestionKind::Single(q) = &kind else {
+                    unreachable!()
+                };
+                assert_eq!(q.variants[q.correct_answer as usize], expected);
+                assert_feedback_aligned(&q.variants, &q.feedback);
+            }
+        }
+    }
+
+    #[test]
+    fn shuffled_multiple_keeps_correct_answers_and_feedback() {
+        for _ in 0..20 {
+            let variants = variants(5);
+            let mut expected = vec![variants[0].clone(), variants[3].clone()];
+            let mut kind = QuestionKind::Multiple(MultipleAnswerQuestion {
+                feedback: feedback(&variants),
+                variants,
+                correct_answers: vec![0, 3],
+            });
+            kind.shuffle();
+            assert!(kind.check(&kind.correct_answer()).unwrap());
+            let QuestionKind::Multiple(q) = &kind else {
+                unreachable!()
+            };
+            let mut correct = q
+                .correct_answers
+                .iter()
+                .map(|index| q.variants[*index as usize].clone())
+                .collect::<Vec<String>>();
+            correct.sort();
+            expected.sort();
+            assert_eq!(correct, expected);
+            assert_feedback_aligned(&q.variants, &q.feedback);
+        }
+    }
+
+    #[test]
+    fn shuffled_ordering_keeps_correct_order() {
+        for _ in 0..20 {
+            let items = variants(4);
+            let correct_order = vec![2, 0, 3, 1];
+            let expected = correct_order
+                .iter()
+                .map(|index| items[*index as usize].clone())
+                .collect::<Vec<String>>();
+            let mut kind = QuestionKind::Ordering(OrderingQuestion {
+                items,
+                correct_order,
+            });
+            kind.shuffle();
+            assert!(kind.check(&kind.correct_answer()).unwrap());
+            let QuestionKind::Ordering(q) = &kind else {
+                unreachable!()
+            };
+            let ordered = q
+                .correct_order
+                .iter()
+                .map(|index| q.items[*index as usize].clone())
+                .collect::<Vec<String>>();
+            assert_eq!(ordered, expected);
+        }
+    }
+
+    #[test]
+    fn shuffle_without_feedback_or_variants() {
+        let mut kind = QuestionKind::Single(SingleAnswerQuestion {
+            variants: variants(3),
+            correct_answer: 1,
+            feedback: Vec::new(),
+        });
+        kind.shuffle();
+        assert!(kind.check(&kind.correct_answer()).unwrap());
+
+        for mut kind in [
+            QuestionKind::Text(TextAnswerQuestion {
+                accepted: vec!["Paris".to_owned()],
+            }),
+            QuestionKind::Numeric(NumericAnswerQuestion {
+                answer: 42.0,
+                tolerance: 0.0,
+            }),
+        ] {
+            kind.shuffle();
+            assert!(kind.check(&kind.correct_answer()).unwrap());
+        }
+    }
+
+    #[test]
+    fn question_without_type_is_single_answer() {
+        let question: Question = serde_json::from_str(
+            r#"{"question": "Old?", "variants": ["a", "b"], "correct_answer": 1}"#,
+        )
+        .unwrap();
+        assert!(matches!(
+            question.kind,
+            QuestionKind::Single(SingleAnswerQuestion {
+                correct_answer: 1,
+                ..
+            })
         ));
+        assert!(question.kind.check(&Answer::Single(1)).unwrap());
+    }
+
+    #[test]
+    fn question_kind_round_trips_with_type() {
+        let question: Question = serde_json::from_str(
+            r#"{"type": "numeric", "question": "Pi?", "answer": 3.0, "tolerance": 0.5}"#,
+        )
+        .unwrap();
+        let json = serde_json::to_value(&question).unwrap();
+        assert_eq!(json["type"], "numeric");
+        let question: Question = serde_json::from_value(json).unwrap();
+        assert!(question.kind.check(&Answer::Numeric(3.2)).unwrap());
+
+        let err = serde_json::from_str::<Question>(r#"{"type": "multiple", "question": "?"}"#)
+            .unwrap_err()
+            .to_string();
+        assert!(err.contains("variants"), "{err}");
     }
 }
@@ -0,0 +1,177 @@
+use chrono::{DateTime, Utc};
+use rand::distributions::Alphanumeric;
+use rand::rngs::OsRng;
+use rand::Rng;
+use serde::Serialize;
+use sqlx::{FromRow, PgExecutor, PgPool};
+use thiserror::Error;
+use uuid::Uuid;
+
+#[derive(Debug, Error)]
+pub enum RewardError {
+    #[error("Reward `{0}` does not exist")]
+    UnknownReward(String),
+    #[error("Reward `{0}` is out of stock")]
+    OutOfStock(String),
+    #[error("Not enough points: the reward costs {cost}, but only {balance} are available")]
+    NotEnoughPoints { cost: i64, balance: i64 },
+    #[error("Invalid redemption code `{0}`")]
+    InvalidCode(String),
+    #[error("Redemption code `{0}` was already used")]
+    AlreadyVerified(String),
+    #[error(transparent)]
+    Sql(#[from] sqlx::Error),
+}
+
+pub type RewardResult<T> = Result<T, RewardError>;
+
+#[derive(Debug, Clone, Serialize, FromRow)]
+pub struct Reward {
+    pub id: String,
+    pub name: String,
+    pub description: Option<String>,
+    pub cost: i32,
+    pub stock: i32,
+    #[serde(skip)]
+    pub event_id: String,
+}
+
+/// A reward handed out to a user, doubles as the audit record of the exchange
+#[derive(Debug, Clone, Serialize, FromRow)]
+pub struct Redemption {
+    pub id: Uuid,
+    pub user_id: Uuid,
+    pub reward_id: String,
+    /// Points deducted, the cost of the reward at the time of the redemption
+    pub cost: i32,
+    /// One-time code the user presents to staff
+    pub code: String,
+    pub redeemed_at: DateTime<Utc>,
+    pub verified_at: Option<DateTime<Utc>>,
+    pub verified_by: Option<String>,
+    pub event_id: String,
+}
+
+/// Length of redemption codes
+const CODE_LENGTH: usize = 8;
+
+pub async fn rewards(pool: &PgPool, event: &str) -> sqlx::Result<Vec<Reward>> {
+    sqlx::query_as::<_, Reward>("SELECT * FROM rewards WHERE event_id = $1 ORDER BY cost, id")
+        .bind(event)
+        .fetch_all(pool)
+        .await
+}
+
+/// Points `user` earned minus points they already spent on rewards
+pub async fn balance<'e>(executor: impl PgExecutor<'e>, user: Uuid) -> sqlx::Result<i64> {
+    sqlx::query_scalar(
+        "SELECT (SELECT COALESCE(SUM(points), 0) FROM answers WHERE user_id = $1)::bigint \
+         - (SELECT COALESCE(SUM(cost), 0) FROM redemptions WHERE user_id = $1)::bigint",
+    )
+    .bind(user)
+    .fetch_one(executor)
+    .await
+}
+
+/// Exchanges points of `user` for `reward`. Deducting the points, taking the reward from stock and
+/// issuing the code happen in a single transaction.
+pub async fn redeem(
+    pool: &PgPool,
+    event: &str,
+    user: Uuid,
+    reward: &str,
+) -> RewardResult<Redemption> {
+    let mut tx = pool.begin().await?;
+    // locking the user serializes their redemptions, so the same points can not be spent twice
+    sqlx::query("SELECT 1 FROM users WHERE id = $1 FOR UPDATE")
+        .bind(user)
+        .execute(&mut tx)
+        .await?;
+    let stored = sqlx::query_as::<_, Reward>(
+        "SELECT * FROM rewards WHERE id = $1 AND event_id = $2 FOR UPDATE",
+    )
+    .bind(reward)
+    .bind(event)
+    .fetch_optional(&mut tx)
+    .await?
+    .ok_or_else(|| RewardError::UnknownReward(reward.to_owned()))?;
+    if stored.stock <= 0 {
+        return Err(RewardError::OutOfStock(stored.id));
+    }
+    let balance = balance(&mut tx, user).await?;
+    if balance < stored.cost as i64 {
+        return Err(RewardError::NotEnoughPoints {
+            cost: stored.cost as i64,
+            balance,
+        });
+    }
+
+    sqlx::query("UPDATE rewards SET stock = stock - 1 WHERE id = $1 AND event_id = $2")
+        .bind(&stored.id)
+        .bind(event)
+        .execute(&mut tx)
+        .await?;
+    let code = OsRng
+        .sample_iter(&Alphanumeric)
+        .take(CODE_LENGTH)
+        .map(char::from)
+        .collect::<String>()
+        .to_uppercase();
+    let redemption = sqlx::query_as::<_, Redemption>(
+        "INSERT INTO redemptions(id, user_id, reward_id, cost, code, event_id) \
+         VALUES ($1, $2, $3, $4, $5, $6) RETURNING *",
+    )
+    .bind(Uuid::new_v4())
+    .bind(user)
+    .bind(&stored.id)
+    .bind(stored.cost)
+    .bind(code)
+    .bind(event)
+    .fetch_one(&mut tx)
+    .await?;
+    tx.commit().await?;
+    Ok(redemption)
+}
+
+/// Marks the redemption with `code` as handed out. Every code can only be verified once.
+pub async fn verify(pool: &PgPool, code: &str, staff: Option<&str>) -> RewardResult<Redemption> {
+    let code = code.trim().to_uppercase();
+    let mut tx = pool.begin().await?;
+    let redemption =
+        sqlx::query_as::<_, Redemption>("SELECT * FROM redemptions WHERE code = $1 FOR UPDATE")
+            .bind(&code)
+            .fetch_optional(&mut tx)
+            .await?
+            .ok_or_else(|| RewardError::InvalidCode(code.clone()))?;
+    if redemption.verified_at.is_some() {
+        return Err(RewardError::AlreadyVerified(code));
+    }
+    let redemption = sqlx::query_as::<_, Redemption>(
+        "UPDATE redemptions SET verified_at = now(), verified_by = $2 WHERE id = $1 RETURNING *",
+    )
+    .bind(redemption.id)
+    .bind(staff)
+    .fetch_one(&mut tx)
+    .await?;
+    tx.commit().await?;
+    Ok(redemption)
+}
+
+pub async fn redemptions_of(pool: &PgPool, user: Uuid) -> sqlx::Result<Vec<Redemption>> {
+    sqlx::query_as::<_, Redemption>(
+        "SELECT * FROM redemptions WHERE user_id = $1 ORDER BY redeemed_at",
+    )
+    .bind(user)
+    .fetch_all(pool)
+    .await
+}
+
+/// Every redemption of `event`, oldest first
+pub async fn redemption_history(pool: &PgPool, event: &str) -> sqlx::Result<Vec<Redemption>> {
+    sqlx::query_as::<_, Redemption>(
+        "SELECT * FROM redemptions WHERE event_id = $1 ORDER BY redeemed_at",
+    )
+    .bind(event)
+    .fetch_all(pool)
+    .await
+}
@@ -0,0 +1,149 @@
+use crate::common::bank::QuestionBank;
+use crate::common::models::{EventStatus, StoredEvent};
+use crate::common::quest::QuestTracker;
+use crate::common::questions::{QuizError, QuizHandler, QuizResult};
+use crate::QuizConfig;
+use chrono::Utc;
+use sqlx::PgPool;
+use std::collections::HashMap;
+use std::sync::{Arc, RwLock};
+use std::time::Duration;
+use uuid::Uuid;
+
+/// The event that requests without an explicit event refer to: the one running right now, or the
+/// closest upcoming one if none is running, or the one that finished last if none is planned.
+pub async fn active_event(pool: &PgPool) -> sqlx::Result<Option<StoredEvent>> {
+    sqlx::query_as::<_, StoredEvent>(
+        "SELECT * FROM events ORDER BY \
+         (starts_at <= now() AND now() < ends_at) DESC, \
+         (starts_at > now()) DESC, \
+         CASE WHEN starts_at > now() THEN starts_at END ASC, \
+         ends_at DESC \
+         LIMIT 1",
+    )
+    .fetch_optional(pool)
+    .await
+}
+
+/// Quiz engine and quest tracker bound to the question bank of a single event
+#[derive(Debug, Clone)]
+pub struct EventContext {
+    pub event: StoredEvent,
+    pub quiz: QuizHandler,
+    pub quest: QuestTracker,
+}
+
+impl EventContext {
+    /// Fails unless the event is running right now. Past events stay readable but can not be
+    /// played anymore.
+    pub fn ensure_running(&self) -> QuizResult<()> {
+        match self.event.status(Utc::now()) {
+            EventStatus::NotStarted => Err(QuizError::EventNotStarted {
+                event: self.event.id.clone(),
+                starts_at: self.event.starts_at,
+            }),
+            EventStatus::Running => Ok(()),
+            EventStatus::Finished => Err(QuizError::EventFinished(self.event.id.clone())),
+        }
+    }
+}
+
+/// Resolves which event a request belongs to. Question banks are loaded the first time an event
+/// needs them and are shared by events that use the same folder.
+#[derive(Debug, Clone)]
+pub struct EventSchedule {
+    pool: PgPool,
+    config: Arc<QuizConfig>,
+    banks: Arc<RwLock<HashMap<String, QuestionBank>>>,
+}
+
+impl EventSchedule {
+    pub fn new(pool: PgPool, config: QuizConfig) -> Self {
+        Self {
+            pool,
+            config: Arc::new(config),
+            banks: Arc::new(RwLock::new(HashMap::new())),
+        }
+    }
+
+    pub async fn events(&self) -> QuizResult<Vec<StoredEvent>> {
+        Ok(
+            sqlx::query_as::<_, StoredEvent>("SELECT * FROM events ORDER BY starts_at")
+                .fetch_all(&self.pool)
+                .await?,
+        )
+    }
+
+    /// Looks up the event with `id`, or the active event if no id is given
+    pub async fn resolve(&self, id: Option<&str>) -> QuizResult<StoredEvent> {
+        match id {
+            Some(id) => sqlx::query_as::<_, StoredEvent>("SELECT * FROM events WHERE id = $1")
+                .bind(id)
+                .fetch_optional(&self.pool)
+                .await?
+                .ok_or_else(|| QuizError::UnknownEvent(id.to_owned())),
+            None => active_event(&self.pool)
+                .await?
+                .ok_or(QuizError::NoActiveEvent),
+        }
+    }
+
+    pub async fn active(&self) -> QuizResult<EventContext> {
+        self.context(self.resolve(None).await?).await
+    }
+
+    pub fn config(&self) -> &QuizConfig {
+        &self.config
+    }
+
+    /// Context of the event `user` is registered in
+    pub async fn of_user(&self, user: Uuid) -> QuizResult<EventContext> {
+        let event = sqlx::query_as::<_, StoredEvent>(
+            "SELECT e.* FROM events e JOIN users u ON u.event_id = e.id WHERE u.id = $1",
+        )
+        .bind(user)
+        .fetch_optional(&self.pool)
+        .await?
+        .ok_or(QuizError::UnknownUser(user))?;
+        self.context(event).await
+    }
+
+    pub async fn context(&self, event: StoredEvent) -> QuizResult<EventContext> {
+        let bank = self.bank(&event.questions).await?;
+        Ok(EventContext {
+            quiz: QuizHandler::new(
+                bank.clone(),
+                self.pool.clone(),
+                self.config.clone(),
+                event.id.clone(),
+            ),
+            quest: QuestTracker::new(bank, self.pool.clone()),
+            event,
+        })
+    }
+
+    async fn bank(&self, folder: &str) -> anyhow::Result<QuestionBank> {
+        if let Some(bank) = self
+            .banks
+            .read()
+            .expect("Question bank cache lock poisoned")
+            .get(folder)
+        {
+            return Ok(bank.clone());
+        }
+
+        // Loaded without holding the lock, so requests for other events are not held up by the
+        // file IO. If two requests race here, the bank of whoever inserts first is kept.
+        let loaded = QuestionBank::load(folder).await?;
+        let mut banks = self
+            .banks
+            .write()
+            .expect("Question bank cache lock poisoned");
+        if let Some(bank) = banks.get(folder) {
+            return Ok(bank.clone());
+        }
+        loaded.watch(Duration::from_secs(self.config.reload_interval));
+        banks.insert(folder.to_owned(), loaded.clone());
+        Ok(loaded)
+    }
+}
@@ -0,0 +1,139 @@
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use sqlx::{FromRow, PgExecutor, PgPool};
+use uuid::Uuid;
+
+#[derive(Debug, Clone, Serialize, FromRow)]
+pub struct UserScore {
+    pub points: i64,
+    pub correct_answers: i64,
+    pub total_answers: i64,
+}
+
+#[derive(Debug, Clone, Serialize, FromRow)]
+pub struct CategoryScore {
+    pub category: String,
+    pub points: i64,
+    pub correct_answers: i64,
+    pub total_answers: i64,
+}
+
+#[derive(Debug, Clone, Serialize, FromRow)]
+pub struct LeaderboardEntry {
+    pub rank: i64,
+    pub uuid: Uuid,
+    pub username: String,
+    pub points: i64,
+    pub correct_answers: i64,
+    /// Time of the last correct answer, used to break ties in favour of whoever got there first
+    pub completed_at: Option<DateTime<Utc>>,
+}
+
+pub async fn user_score(pool: &PgPool, user: Uuid) -> sqlx::Result<UserScore> {
+    sqlx::query_as::<_, UserScore>(
+        "SELECT COALESCE(SUM(points), 0)::bigint AS points, \
+         COUNT(*) FILTER (WHERE correct) AS correct_answers, \
+         COUNT(*) AS total_answers \
+         FROM answers WHERE user_id = $1",
+    )
+    .bind(user)
+    .fetch_one(pool)
+    .await
+}
+
+pub async fn category_scores(
+    executor: impl PgExecutor<'_>,
+    user: Uuid,
+) -> sqlx::Result<Vec<CategoryScore>> {
+    sqlx::query_as::<_, CategoryScore>(
+        "SELECT category, COALESCE(SUM(points), 0)::bigint AS points, \
+         COUNT(*) FILTER (WHERE correct) AS correct_answers, \
+         COUNT(*) AS total_answers \
+         FROM answers WHERE user_id = $1 GROUP BY category ORDER BY category",
+    )
+    .bind(user)
+    .fetch_all(executor)
+    .await
+}
+
+/// Ranks all users registered in `event` by points, then correct answers, then earliest completion
+/// time. Only answers from `category` are counted when it is provided.
+pub async fn leaderboard(
+    pool: &PgPool,
+    event: &str,
+    category: Option<&str>,
+    limit: i64,
+    offset: i64,
+) -> sqlx::Result<Vec<LeaderboardEntry>> {
+    sqlx::query_as::<_, LeaderboardEntry>(
+        "SELECT RANK() OVER (ORDER BY points DESC, correct_answers DESC, completed_at ASC NULLS LAST) AS rank, \
+         uuid, username, points, correct_answers, completed_at FROM ( \
+             SELECT u.id AS uuid, u.username, \
+             COALESCE(SUM(a.points), 0)::bigint AS points, \
+             COUNT(a.question_id) FILTER (WHERE a.correct) AS correct_answers, \
+             MAX(a.answered_at) FILTER (WHERE a.correct) AS completed_at \
+             FROM users u LEFT JOIN answers a ON a.user_id = u.id AND ($2::varchar IS NULL OR a.category = $2) \
+             WHERE u.event_id = $1 \
+             GROUP BY u.id, u.username \
+         ) scores ORDER BY rank, username LIMIT $3 OFFSET $4",
+    )
+    .bind(event)
+    .bind(category)
+    .bind(limit)
+    .bind(offset)
+    .fetch_all(pool)
+    .await
+}
+
+#[derive(Debug, Clone, Serialize, FromRow)]
+pub struct TeamLeaderboardEntry {
+    pub rank: i64,
+    pub uuid: Uuid,
+    pub name: String,
+    pub members: i64,
+    pub points: i64,
+    pub correct_answers: i64,
+    pub completed_at: Option<DateTime<Utc>>,
+}
+
+/// Sum of the scores of all current members of `team`
+pub async fn team_score(pool: &PgPool, team: Uuid) -> sqlx::Result<UserScore> {
+    sqlx::query_as::<_, UserScore>(
+        "SELECT COALESCE(SUM(a.points), 0)::bigint AS points, \
+         COUNT(*) FILTER (WHERE a.correct) AS correct_answers, \
+         COUNT(*) AS total_answers \
+         FROM answers a JOIN team_members m ON m.user_id = a.user_id WHERE m.team_id = $1",
+    )
+    .bind(team)
+    .fetch_one(pool)
+    .await
+}
+
+/// Ranks teams of `event` the same way as [leaderboard], counting the answers of their current
+/// members
+pub async fn team_leaderboard(
+    pool: &PgPool,
+    event: &str,
+    limit: i64,
+    offset: i64,
+) -> sqlx::Result<Vec<TeamLeaderboardEntry>> {
+    sqlx::query_as::<_, TeamLeaderboardEntry>(
+        "SELECT RANK() OVER (ORDER BY points DESC, correct_answers DESC, completed_at ASC NULLS LAST) AS rank, \
+         uuid, name, members, points, correct_answers, completed_at FROM ( \
+             SELECT t.id AS uuid, t.name, \
+             COUNT(DISTINCT m.user_id) AS members, \
+             COALESCE(SUM(a.points), 0)::bigint AS points, \
+             COUNT(a.question_id) FILTER (WHERE a.correct) AS correct_answers, \
+             MAX(a.answered_at) FILTER (WHERE a.correct) AS completed_at \
+             FROM teams t JOIN team_members m ON m.team_id = t.id \
+             LEFT JOIN answers a ON a.user_id = m.user_id \
+             WHERE t.event_id = $1 \
+             GROUP BY t.id, t.name \
+         ) scores ORDER BY rank, name LIMIT $2 OFFSET $3",
+    )
+    .bind(event)
+    .bind(limit)
+    .bind(offset)
+    .fetch_all(pool)
+    .await
+}
@@ -0,0 +1,232 @@
+use crate::TeamConfig;
+use rand::distributions::Alphanumeric;
+use rand::rngs::OsRng;
+use rand::Rng;
+use serde::Serialize;
+use sqlx::{FromRow, PgPool};
+use thiserror::Error;
+use uuid::Uuid;
+
+#[derive(Debug, Error)]
+pub enum TeamError {
+    #[error("User is already in a team")]
+    AlreadyInTeam,
+    #[error("User is not in a team")]
+    NotInTeam,
+    #[error("Team name `{0}` is already taken")]
+    NameTaken(String),
+    #[error("Invalid team name")]
+    InvalidName,
+    #[error("Invalid team code `{0}`")]
+    InvalidCode(String),
+    #[error("Team is full, at most {0} members are allowed")]
+    TeamFull(u32),
+    #[error("Only the team captain can do this")]
+    NotCaptain,
+    #[error("User `{0}` is not a member of this team")]
+    UnknownMember(String),
+    #[error(transparent)]
+    Sql(#[from] sqlx::Error),
+}
+
+pub type TeamResult<T> = Result<T, TeamError>;
+
+#[derive(Debug, Clone, Serialize, FromRow)]
+pub struct Team {
+    #[serde(rename = "uuid")]
+    pub id: Uuid,
+    pub name: String,
+    #[serde(skip)]
+    pub code: String,
+    pub event_id: String,
+}
+
+#[derive(Debug, Clone, Serialize, FromRow)]
+pub struct TeamMember {
+    pub uuid: Uuid,
+    pub username: String,
+    pub captain: bool,
+}
+
+/// Length of the code other players use to join a team
+const CODE_LENGTH: usize = 6;
+
+pub async fn team_of(pool: &PgPool, user: Uuid) -> sqlx::Result<Option<Team>> {
+    sqlx::query_as::<_, Team>(
+        "SELECT t.* FROM teams t JOIN team_members m ON m.team_id = t.id WHERE m.user_id = $1",
+    )
+    .bind(user)
+    .fetch_optional(pool)
+    .await
+}
+
+pub async fn team_by_id(pool: &PgPool, team: Uuid) -> sqlx::Result<Option<Team>> {
+    sqlx::query_as::<_, Team>("SELECT * FROM teams WHERE id = $1")
+        .bind(team)
+        .fetch_optional(pool)
+        .await
+}
+
+pub async fn members(pool: &PgPool, team: Uuid) -> sqlx::Result<Vec<TeamMember>> {
+    sqlx::query_as::<_, TeamMember>(
+        "SELECT u.id AS uuid, u.username, m.captain FROM team_members m JOIN users u ON u.id = m.user_id \
+         WHERE m.team_id = $1 ORDER BY m.joined_at",
+    )
+    .bind(team)
+    .fetch_all(pool)
+    .await
+}
+
+pub async fn create_team(
+    pool: &PgPool,
+    config: &TeamConfig,
+    event: &str,
+    user: Uuid,
+    name: &str,
+) -> TeamResult<Team> {
+    let name = name.trim();
+    if name.is_empty() || name.chars().count() > 32 {
+        return Err(TeamError::InvalidName);
+    }
+    let mut tx = pool.begin().await?;
+    if sqlx::query("SELECT 1 FROM team_members WHERE user_id = $1")
+        .bind(user)
+        .fetch_optional(&mut tx)
+        .await?
+        .is_some()
+    {
+        return Err(TeamError::AlreadyInTeam);
+    }
+    if sqlx::query("SELECT 1 FROM teams WHERE event_id = $1 AND lower(name) = lower($2)")
+        .bind(event)
+        .bind(name)
+        .fetch_optional(&mut tx)
+        .await?
+        .is_some()
+    {
+        return Err(TeamError::NameTaken(name.to_owned()));
+    }
+
+    let code = OsRng
+        .sample_iter(&Alphanumeric)
+        .take(CODE_LENGTH)
+        .map(char::from)
+        .collect::<String>()
+        .to_uppercase();
+    let team = sqlx::query_as::<_, Team>(
+        "INSERT INTO teams(id, name, code, event_id) VALUES ($1, $2, $3, $4) RETURNING *",
+    )
+    .bind(Uuid::new_v4())
+    .bind(name)
+    .bind(&code)
+    .bind(event)
+    .fetch_one(&mut tx)
+    .await?;
+    sqlx::query("INSERT INTO team_members VALUES ($1, $2, $3)")
+        .bind(user)
+        .bind(team.id)
+        .bind(config.captains)
+        .execute(&mut tx)
+        .await?;
+    tx.commit().await?;
+    Ok(team)
+}
+
+pub async fn join_team(
+    pool: &PgPool,
+    config: &TeamConfig,
+    event: &str,
+    user: Uuid,
+    code: &str,
+) -> TeamResult<Team> {
+    let code = code.trim().to_uppercase();
+    let mut tx = pool.begin().await?;
+    if sqlx::query("SELECT 1 FROM team_members WHERE user_id = $1")
+        .bind(user)
+        .fetch_optional(&mut tx)
+        .await?
+        .is_some()
+    {
+        return Err(TeamError::AlreadyInTeam);
+    }
+    // locking the team row keeps concurrent joins from going over the size limit
+    let team = sqlx::query_as::<_, Team>(
+        "SELECT * FROM teams WHERE code = $1 AND event_id = $2 FOR UPDATE",
+    )
+    .bind(&code)
+    .bind(event)
+    .fetch_optional(&mut tx)
+    .await?
+    .ok_or(TeamError::InvalidCode(code))?;
+    let size: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM team_members WHERE team_id = $1")
+        .bind(team.id)
+        .fetch_one(&mut tx)
+        .await?;
+    if size >= config.max_members as i64 {
+        return Err(TeamError::TeamFull(config.max_members));
+    }
+    sqlx::query("INSERT INTO team_members VALUES ($1, $2, false)")
+        .bind(user)
+        .bind(team.id)
+        .execute(&mut tx)
+        .await?;
+    tx.commit().await?;
+    Ok(team)
+}
+
+/// Removes `user` from their team. The longest standing member becomes captain if the captain
+/// leaves, and the team is deleted once its last member is gone.
+pub async fn leave_team(pool: &PgPool, user: Uuid) -> TeamResult<Team> {
+    let team = team_of(pool, user).await?.ok_or(TeamError::NotInTeam)?;
+    remove_member(pool, &team, user).await?;
+    Ok(team)
+}
+
+/// Removes the member with `username` from the team of `captain`
+pub async fn kick_member(
+    pool: &PgPool,
+    config: &TeamConfig,
+    captain: Uuid,
+    username: &str,
+) -> TeamResult<Team> {
+    if !config.captains {
+        return Err(TeamError::NotCaptain);
+    }
+    let team = team_of(pool, captain).await?.ok_or(TeamError::NotInTeam)?;
+    let members = members(pool, team.id).await?;
+    if !members.iter().any(|m| m.uuid == captain && m.captain) {
+        return Err(TeamError::NotCaptain);
+    }
+    let member = members
+        .iter()
+        .find(|m| m.username == username && m.uuid != captain)
+        .ok_or_else(|| TeamError::UnknownMember(username.to_owned()))?;
+    remove_member(pool, &team, member.uuid).await?;
+    Ok(team)
+}
+
+async fn remove_member(pool: &PgPool, team: &Team, user: Uuid) -> TeamResult<()> {
+    let mut tx = pool.begin().await?;
+    let was_captain: bool =
+        sqlx::query_scalar("DELETE FROM team_members WHERE user_id = $1 RETURNING captain")
+            .bind(user)
+            .fetch_one(&mut tx)
+            .await?;
+    if was_captain {
+        sqlx::query(
+            "UPDATE team_members SET captain = true WHERE user_id = \
+             (SELECT user_id FROM team_members WHERE team_id = $1 ORDER BY joined_at LIMIT 1)",
+        )
+        .bind(team.id)
+        .execute(&mut tx)
+        .await?;
+    }
+    sqlx::query(
+        "DELETE FROM teams WHERE id = $1 AND NOT EXISTS (SELECT 1 FROM team_members WHERE team_id = $1)",
+    )
+    .bind(team.id)
+    .execute(&mut tx)
+    .await?;
+    tx.commit().await?;
+    Ok(())
+}
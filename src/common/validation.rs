@@ -0,0 +1,179 @@
+use crate::common::questions::{Question, QuestionKind};
+use serde::Serialize;
+use std::collections::HashSet;
+use std::fmt::{Display, Formatter};
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ValidationError {
+    pub category: String,
+    /// Index of the offending question, or `None` if the whole file is affected
+    pub index: Option<usize>,
+    pub message: String,
+}
+
+impl Display for ValidationError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self.index {
+            Some(index) => write!(f, "{}.json[{}]: {}", self.category, index, self.message),
+            None => write!(f, "{}.json: {}", self.category, self.message),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct CategoryReport {
+    pub category: String,
+    pub questions: usize,
+    pub errors: Vec<ValidationError>,
+}
+
+/// Longest question id that fits into the question history
+const MAX_ID_LENGTH: usize = 64;
+
+/// Parses and validates the contents of a category file.
+///
+/// All answer indices in the question bank are 0-based.
+pub fn validate_category(
+    category: &str,
+    source: &str,
+) -> Result<Vec<Question>, Vec<ValidationError>> {
+    let error = |index: Option<usize>, message: String| ValidationError {
+        category: category.to_owned(),
+        index,
+        message,
+    };
+
+    let questions: Vec<Question> = serde_json::from_str(source)
+        .map_err(|err| vec![error(None, format!("Malformed JSON: {err}"))])?;
+    if questions.is_empty() {
+        return Err(vec![error(
+            None,
+            "Category contains no questions".to_owned(),
+        )]);
+    }
+
+    let mut errors = Vec::new();
+    let mut seen = HashSet::new();
+    let mut ids = HashSet::new();
+    for (index, question) in questions.iter().enumerate() {
+        if let Some(id) = &question.id {
+            if id.trim().is_empty() || id.len() > MAX_ID_LENGTH {
+                errors.push(error(
+                    Some(index),
+                    format!("Question id has to be 1 to {MAX_ID_LENGTH} bytes long"),
+                ));
+            } else if !ids.insert(id) {
+                errors.push(error(Some(index), format!("Duplicate question id `{id}`")));
+            }
+        }
+        if question.question.trim().is_empty() {
+            errors.push(error(Some(index), "Question text is empty".to_owned()));
+        } else if !seen.insert(question.question.trim().to_lowercase()) {
+            errors.push(error(
+                Some(index),
+                format!("Duplicate question `{}`", question.question),
+            ));
+        }
+        errors.extend(
+            validate_kind(&question.kind)
+                .into_iter()
+                .map(|message| error(Some(index), message)),
+        );
+    }
+
+    if errors.is_empty() {
+        Ok(questions)
+    } else {
+        Err(errors)
+    }
+}
+
+fn validate_kind(kind: &QuestionKind) -> Vec<String> {
+    let mut errors = Vec::new();
+    match kind {
+        QuestionKind::Single(q) => {
+            validate_variants("variants", &q.variants, &mut errors);
+            validate_index(
+                "correct_answer",
+                q.correct_answer,
+                q.variants.len(),
+                &mut errors,
+            );
+            validate_feedback(&q.feedback, q.variants.len(), &mut errors);
+        }
+        QuestionKind::Multiple(q) => {
+            validate_variants("variants", &q.variants, &mut errors);
+            if q.correct_answers.is_empty() {
+                errors.push("`correct_answers` is empty".to_owned());
+            }
+            if !all_unique(&q.correct_answers) {
+                errors.push("`correct_answers` contains duplicates".to_owned());
+            }
+            for answer in &q.correct_answers {
+                validate_index("correct_answers", *answer, q.variants.len(), &mut errors);
+            }
+            validate_feedback(&q.feedback, q.variants.len(), &mut errors);
+        }
+        QuestionKind::Text(q) => {
+            if q.accepted.is_empty() {
+                errors.push("`accepted` is empty".to_owned());
+            }
+            if q.accepted.iter().any(|answer| answer.trim().is_empty()) {
+                errors.push("`accepted` contains a blank answer".to_owned());
+            }
+        }
+        QuestionKind::Numeric(q) => {
+            if !q.answer.is_finite() {
+                errors.push("`answer` is not a finite number".to_owned());
+            }
+            if !q.tolerance.is_finite() || q.tolerance < 0.0 {
+                errors.push("`tolerance` has to be a non-negative number".to_owned());
+            }
+        }
+        QuestionKind::Ordering(q) => {
+            validate_variants("items", &q.items, &mut errors);
+            let mut order = q.correct_order.clone();
+            order.sort_unstable();
+            if q.items.len() > u8::MAX as usize || !order.iter().copied().eq(0..q.items.len() as u8)
+            {
+                errors.push("`correct_order` is not a permutation of `items` indices".to_owned());
+            }
+        }
+    }
+    errors
+}
+
+fn validate_feedback(feedback: &[Option<String>], variants: usize, errors: &mut Vec<String>) {
+    if !feedback.is_empty() && feedback.len() != variants {
+        errors.push(format!(
+            "`feedback` has {} entries, but there are {variants} variants",
+            feedback.len()
+        ));
+    }
+}
+
+fn validate_variants(field: &str, variants: &[String], errors: &mut Vec<String>) {
+    if variants.is_empty() {
+        errors.push(format!("`{field}` is empty"));
+    }
+    if variants.iter().any(|variant| variant.trim().is_empty()) {
+        errors.push(format!("`{field}` contains a blank entry"));
+    }
+    if !all_unique(variants) {
+        errors.push(format!("`{field}` contains duplicates"));
+    }
+}
+
+fn validate_index(field: &str, index: u8, len: usize, errors: &mut Vec<String>) {
+    if index as usize >= len {
+        errors.push(format!(
+            "`{field}` index {index} is out of range, expected 0 to {}",
+            len.saturating_sub(1)
+        ));
+    }
+}
+
+fn all_unique<T: Eq + std::hash::Hash>(values: &[T]) -> bool {
+    let mut seen = HashSet::new();
+    values.iter().all(|value| seen.insert(value))
+}
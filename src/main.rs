@@ -1,3 +1,4 @@
+use crate::common::events::EventBus;
 use crate::server::init_server;
 use crate::tg::init_tg;
 use log::LevelFilter;
@@ -12,6 +13,7 @@ use log4rs::{init_config, Config};
 use serde::{Deserialize, Serialize};
 use sqlx::postgres::{PgConnectOptions, PgPoolOptions};
 use sqlx::ConnectOptions;
+use std::collections::HashMap;
 use std::path::Path;
 use tokio::fs::File;
 use tokio::io::{AsyncReadExt, AsyncWriteExt};
@@ -83,7 +85,7 @@ async fn main() -> anyhow::Result<()> {
     let key = cfg.telegram.api_key.clone();
 
     let pool = PgPoolOptions::new()
-        .max_connections(4)
+        .max_connections(cfg.postgres.max_connections)
         .connect_with(
             PgConnectOptions::new()
                 .host(&cfg.postgres.host)
@@ -95,14 +97,18 @@ async fn main() -> anyhow::Result<()> {
         )
         .await?;
 
+    let events = EventBus::new(256);
+
     let pc = pool.clone();
+    let ec = events.clone();
+    let teams = cfg.teams.clone();
     let tg_handle = tokio::spawn(async move {
-        init_tg(key, pc)
+        init_tg(key, pc, ec, teams)
             .await
             .expect("Could not initialize telegram bot!")
     });
     let server_handle = tokio::spawn(async move {
-        init_server(&cfg, pool)
+        init_server(&cfg, pool, events)
             .await
             .expect("Could not initialize server!")
     });
@@ -120,6 +126,10 @@ pub struct ServerConfig {
     api: ApiConfig,
     telegram: TelegramConfig,
     postgres: PostgresConfig,
+    #[serde(default)]
+    quiz: QuizConfig,
+    #[serde(default)]
+    teams: TeamConfig,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -140,6 +150,86 @@ pub struct PostgresConfig {
     host: String,
     username: String,
     password: String,
+    #[serde(default = "default_max_connections")]
+    max_connections: u32,
+}
+
+fn default_max_connections() -> u32 {
+    16
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct QuizConfig {
+    /// Points awarded for a correct answer when neither the question nor its category override it
+    default_points: u32,
+    /// Per-category overrides of `default_points`
+    category_points: HashMap<String, u32>,
+    /// How often the question folder is checked for changes, in seconds
+    reload_interval: u64,
+    /// Seconds given to answer a question when neither the question nor its category override it
+    default_time_limit: Option<u64>,
+    /// Per-category overrides of `default_time_limit`
+    category_time_limits: HashMap<String, u64>,
+    /// How often expired unanswered questions are purged, in seconds
+    gc_interval: u64,
+    /// Seconds expired questions are kept before being purged, late answers are told that the
+    /// question expired until then
+    gc_grace_period: u64,
+    /// Added to the points multiplier for every previous consecutive correct answer in a category
+    streak_step: f64,
+    /// Upper bound of the streak multiplier
+    max_streak_multiplier: f64,
+    /// Extra multiplier for an instant correct answer, shrinking linearly to nothing at
+    /// `time_bonus_window`
+    time_bonus: f64,
+    /// Seconds after a question is issued during which a correct answer earns a time bonus
+    time_bonus_window: u64,
+    /// Share of the points deducted from a correct answer for every revealed hint
+    hint_penalty: f64,
+    /// 50/50 lifelines every user gets per event
+    fifty_fifty_lifelines: u32,
+    /// Skip lifelines every user gets per event
+    skip_lifelines: u32,
+}
+
+impl Default for QuizConfig {
+    fn default() -> Self {
+        QuizConfig {
+            default_points: 1,
+            category_points: HashMap::new(),
+            reload_interval: 5,
+            default_time_limit: Some(300),
+            category_time_limits: HashMap::new(),
+            gc_interval: 60,
+            gc_grace_period: 3600,
+            streak_step: 0.0,
+            max_streak_multiplier: 2.0,
+            time_bonus: 0.0,
+            time_bonus_window: 30,
+            hint_penalty: 0.25,
+            fifty_fifty_lifelines: 1,
+            skip_lifelines: 1,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct TeamConfig {
+    /// Maximum amount of members in a single team, captain included
+    max_members: u32,
+    /// Whether team creators become captains that are allowed to kick members
+    captains: bool,
+}
+
+impl Default for TeamConfig {
+    fn default() -> Self {
+        TeamConfig {
+            max_members: 5,
+            captains: true,
+        }
+    }
 }
 
 impl Default for ServerConfig {
@@ -158,7 +248,10 @@ impl Default for ServerConfig {
                 host: "localhost".to_string(),
                 username: "<USERNAME>".to_string(),
                 password: "<PASSWORD>".to_string(),
+                max_connections: default_max_connections(),
             },
+            quiz: QuizConfig::default(),
+            teams: TeamConfig::default(),
         }
     }
 }
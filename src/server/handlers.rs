@@ -1,18 +1,38 @@
-use axum::extract::rejection::{JsonRejection, PathRejection};
-use axum::extract::Path;
+use axum::extract::rejection::{JsonRejection, PathRejection, QueryRejection};
+use axum::extract::{Path, Query};
+use axum::response::sse::{Event, KeepAlive, Sse};
 use axum::response::{IntoResponse, Response};
 use axum::{Extension, Json};
 use axum_extra::extract::WithRejection;
+use futures_lite::{stream, Stream};
 use std::io;
-use std::sync::Arc;
 // use axum_extra::extract::WithRejection;
-use crate::common::models::StoredUser;
-use crate::common::questions::{QuestionInstance, QuizHandler};
-use crate::server::models::{AnswerResponse, Maybe, RegistrationResponse, UserData};
+use crate::common::events::{EventBus, QuestEvent};
+use crate::common::lifelines::lifelines_of;
+use crate::common::models::{StoredStation, StoredUser};
+use crate::common::quest::QuestProgress;
+use crate::common::questions::{Answer, Hint, QuizError};
+use crate::common::rewards::{
+    balance, redeem, redemption_history, redemptions_of, rewards, verify, RewardError,
+};
+use crate::common::schedule::{EventContext, EventSchedule};
+use crate::common::scores::{
+    category_scores, leaderboard, team_leaderboard, team_score, user_score,
+};
+use crate::common::teams::{members, team_by_id, team_of};
+use crate::server::models::{
+    AchievementsResponse, AnswerResponse, CategoriesQuery, CategoriesResponse, EventQuery,
+    EventResponse, EventsResponse, HintQuery, IssuedQuestion, LeaderboardResponse,
+    LifelineResponse, Maybe, Paging, RedemptionResponse, RedemptionsResponse, RegistrationResponse,
+    RewardsResponse, ScoreResponse, StationTapResponse, StatusResponse, TeamLeaderboardResponse,
+    TeamResponse, UserData, ValidationResponse, VerifyQuery,
+};
+use crate::QuizConfig;
+use chrono::{DateTime, Utc};
 use serde::{Serialize, Serializer};
 use sqlx::PgPool;
 use thiserror::Error;
-use tokio::sync::Mutex;
+use tokio::sync::broadcast::error::RecvError;
 use uuid::Uuid;
 
 #[derive(Debug, Error)]
@@ -27,6 +47,8 @@ pub enum ServerError {
     InvalidPostData(#[from] JsonRejection),
     #[error("Invalid data format in path: `{0}`")]
     InvalidPathData(#[from] PathRejection),
+    #[error("Invalid query parameters: `{0}`")]
+    InvalidQueryData(#[from] QueryRejection),
     #[error("Data not found: `{0}`")]
     NotFound(String),
     #[error("Could not parse value: `{0}`")]
@@ -39,6 +61,121 @@ pub enum ServerError {
     ShaError,
     #[error("User with card SHA `{0}` already exists!")]
     UserExists(String),
+    #[error("Question `{0}` was not issued to user `{1}`")]
+    QuestionNotBound(Uuid, Uuid),
+    #[error("Category `{0}` is locked, fulfill its requirements first")]
+    CategoryLocked(String),
+    #[error("Category `{0}` is complete, there are no more questions left")]
+    CategoryComplete(String),
+    #[error("Station `{0}` is complete, there are no more questions left")]
+    StationComplete(String),
+    #[error("Station `{0}` is not active right now")]
+    StationInactive(String),
+    #[error("Station `{0}` is not unlocked yet, complete the previous stages of the quest first")]
+    StationLocked(String),
+    #[error("Time to answer question `{0}` has run out")]
+    QuestionExpired(Uuid),
+    #[error("All hints of question `{0}` were already revealed")]
+    NoHintsLeft(Uuid),
+    #[error("There are no {0} lifelines left")]
+    NoLifelinesLeft(String),
+    #[error("The {0} lifeline can not be used on question `{1}`")]
+    LifelineNotApplicable(String, Uuid),
+    #[error("Invalid answer: `{0}`")]
+    InvalidAnswer(String),
+    #[error("Category `{0}` is invalid and can not be served: {1}")]
+    InvalidCategory(String, String),
+    #[error("There is no active event")]
+    NoActiveEvent,
+    #[error("Event `{0}` is over, its data can only be read")]
+    EventReadOnly(String),
+    #[error("The quest has not started yet, it starts at {0}")]
+    QuestNotStarted(DateTime<Utc>),
+    #[error("The quest is finished")]
+    QuestFinished,
+    #[error("Category `{0}` is not open yet, it opens at {1}")]
+    CategoryNotOpen(String, DateTime<Utc>),
+    #[error("Category `{0}` is closed")]
+    CategoryClosed(String),
+    #[error("Reward `{0}` is out of stock")]
+    OutOfStock(String),
+    #[error("Not enough points: the reward costs {0}, but only {1} are available")]
+    NotEnoughPoints(i64, i64),
+    #[error("Redemption code `{0}` was already used")]
+    CodeAlreadyUsed(String),
+    #[error("Rewards of event `{0}` can not be redeemed anymore")]
+    RedemptionClosed(String),
+}
+
+impl From<QuizError> for ServerError {
+    fn from(err: QuizError) -> Self {
+        match err {
+            QuizError::InvalidQuestion(id) => {
+                ServerError::NotFound(format!("Could not find unanswered question `{id}`"))
+            }
+            QuizError::NotBound { question, user } => ServerError::QuestionNotBound(question, user),
+            QuizError::UnknownCategory(category) => {
+                ServerError::NotFound(format!("Could not find question category `{category}`"))
+            }
+            QuizError::InvalidCategory { category, errors } => ServerError::InvalidCategory(
+                category,
+                errors
+                    .iter()
+                    .map(ToString::to_string)
+                    .collect::<Vec<String>>()
+                    .join("; "),
+            ),
+            QuizError::CategoryLocked(category) => ServerError::CategoryLocked(category),
+            QuizError::CategoryComplete(category) => ServerError::CategoryComplete(category),
+            QuizError::StationComplete(station) => ServerError::StationComplete(station),
+            QuizError::StationLocked(station) => ServerError::StationLocked(station),
+            QuizError::Expired(question) => ServerError::QuestionExpired(question),
+            QuizError::NoHintsLeft(question) => ServerError::NoHintsLeft(question),
+            QuizError::NoLifelinesLeft(lifeline) => {
+                ServerError::NoLifelinesLeft(lifeline.to_string())
+            }
+            QuizError::LifelineNotApplicable { lifeline, question } => {
+                ServerError::LifelineNotApplicable(lifeline.to_string(), question)
+            }
+            err @ QuizError::AnswerTypeMismatch { .. } => {
+                ServerError::InvalidAnswer(err.to_string())
+            }
+            QuizError::UnknownUser(user) => ServerError::NotFound(format!(
+                "Could not find user with UUID of `{user}` in the database!"
+            )),
+            QuizError::UnknownEvent(event) => {
+                ServerError::NotFound(format!("Could not find event `{event}` in the database!"))
+            }
+            QuizError::NoActiveEvent => ServerError::NoActiveEvent,
+            QuizError::EventNotStarted { starts_at, .. } => ServerError::QuestNotStarted(starts_at),
+            QuizError::EventFinished(_) => ServerError::QuestFinished,
+            QuizError::CategoryNotOpen { category, opens_at } => {
+                ServerError::CategoryNotOpen(category, opens_at)
+            }
+            QuizError::CategoryClosed(category) => ServerError::CategoryClosed(category),
+            QuizError::Sql(err) => ServerError::SqlError(err),
+            QuizError::Other(err) => ServerError::DelegatedError(err),
+        }
+    }
+}
+
+impl From<RewardError> for ServerError {
+    fn from(err: RewardError) -> Self {
+        match err {
+            RewardError::UnknownReward(reward) => {
+                ServerError::NotFound(format!("Could not find reward `{reward}` in the database!"))
+            }
+            RewardError::OutOfStock(reward) => ServerError::OutOfStock(reward),
+            RewardError::NotEnoughPoints { cost, balance } => {
+                ServerError::NotEnoughPoints(cost, balance)
+            }
+            RewardError::InvalidCode(code) => {
+                ServerError::NotFound(format!("Could not find redemption code `{code}`"))
+            }
+            RewardError::AlreadyVerified(code) => ServerError::CodeAlreadyUsed(code),
+            RewardError::Sql(err) => ServerError::SqlError(err),
+        }
+    }
 }
 
 impl Serialize for ServerError {
@@ -56,6 +193,9 @@ impl IntoResponse for ServerError {
     }
 }
 
+/// Amount of leaderboard entries pushed with every `leaderboard_changed` event
+const LIVE_LEADERBOARD_SIZE: i64 = 10;
+
 pub type Payload<T> = axum::response::Result<Json<Maybe<T>>, ServerError>;
 
 pub fn success<T>(value: T) -> Payload<T> {
@@ -66,20 +206,36 @@ pub fn err<T>(err: ServerError) -> Payload<T> {
     Ok(Json(Maybe::failure(err)))
 }
 
+async fn user_data(
+    pool: &PgPool,
+    config: &QuizConfig,
+    user: StoredUser,
+) -> Result<UserData, ServerError> {
+    let score = user_score(pool, user.id).await?;
+    let team = team_of(pool, user.id).await?;
+    let lifelines = lifelines_of(pool, config, user.id).await?;
+    Ok(UserData {
+        username: user.username,
+        card_hash: user.card_hash,
+        uuid: user.id,
+        event: user.event_id,
+        score,
+        team,
+        lifelines,
+    })
+}
+
 pub async fn get_user_id(
     Path(id): Path<Uuid>,
     Extension(pool): Extension<PgPool>,
+    Extension(schedule): Extension<EventSchedule>,
 ) -> Payload<UserData> {
     if let Some(user) = sqlx::query_as::<_, StoredUser>("SELECT * FROM users WHERE id = $1")
-        .bind(&id)
+        .bind(id)
         .fetch_optional(&pool)
         .await?
     {
-        success(UserData {
-            username: user.username,
-            card_hash: user.card_hash,
-            uuid: user.id,
-        })
+        success(user_data(&pool, schedule.config(), user).await?)
     } else {
         err(ServerError::NotFound(format!(
             "Could not find user with UUID of `{id}` in the database!"
@@ -89,18 +245,20 @@ pub async fn get_user_id(
 
 pub async fn get_user_sha(
     Path(sha): Path<String>,
+    WithRejection(Query(query), _): WithRejection<Query<EventQuery>, ServerError>,
     Extension(pool): Extension<PgPool>,
+    Extension(schedule): Extension<EventSchedule>,
 ) -> Payload<UserData> {
-    if let Some(user) = sqlx::query_as::<_, StoredUser>("SELECT * FROM users WHERE card_hash = $1")
-        .bind(&sha)
-        .fetch_optional(&pool)
-        .await?
+    let event = schedule.resolve(query.event.as_deref()).await?;
+    if let Some(user) = sqlx::query_as::<_, StoredUser>(
+        "SELECT * FROM users WHERE event_id = $1 AND card_hash = $2",
+    )
+    .bind(&event.id)
+    .bind(&sha)
+    .fetch_optional(&pool)
+    .await?
     {
-        success(UserData {
-            username: user.username,
-            card_hash: user.card_hash,
-            uuid: user.id,
-        })
+        success(user_data(&pool, schedule.config(), user).await?)
     } else {
         err(ServerError::NotFound(format!(
             "Could not find user with SHA256 card hash of `{sha}` in the database!"
@@ -111,11 +269,17 @@ pub async fn get_user_sha(
 pub async fn begin_registration(
     WithRejection(Path(sha), _): WithRejection<Path<String>, ServerError>,
     Extension(pool): Extension<PgPool>,
+    Extension(schedule): Extension<EventSchedule>,
 ) -> Payload<RegistrationResponse> {
     if sha.len() != 64 {
         return err(ServerError::ShaError);
     }
-    if sqlx::query_as::<_, StoredUser>("SELECT * FROM users WHERE card_hash = $1")
+    let event = schedule.resolve(None).await?;
+    if event.is_finished(Utc::now()) {
+        return err(ServerError::EventReadOnly(event.id));
+    }
+    if sqlx::query_as::<_, StoredUser>("SELECT * FROM users WHERE event_id = $1 AND card_hash = $2")
+        .bind(&event.id)
         .bind(&sha)
         .fetch_optional(&pool)
         .await?
@@ -123,11 +287,15 @@ pub async fn begin_registration(
     {
         return err(ServerError::UserExists(sha));
     }
-    let rows = sqlx::query("INSERT INTO users_reg VALUES ($1, $2)")
-        .bind(&sha)
-        .bind(Uuid::new_v4())
-        .execute(&pool)
-        .await?;
+    let rows = sqlx::query(
+        "INSERT INTO users_reg VALUES ($1, $2, $3) \
+         ON CONFLICT (event_id, hash) DO UPDATE SET id = excluded.id",
+    )
+    .bind(&sha)
+    .bind(Uuid::new_v4())
+    .bind(&event.id)
+    .execute(&pool)
+    .await?;
     if rows.rows_affected() < 1 {
         log::warn!("Invalid amount of rows affected for user registration process initialization.")
     }
@@ -139,23 +307,423 @@ pub async fn begin_registration(
 
 pub async fn get_question(
     WithRejection(Path((user, category)), _): WithRejection<Path<(Uuid, String)>, ServerError>,
-    Extension(quiz): Extension<Arc<Mutex<QuizHandler>>>,
-) -> Payload<QuestionInstance> {
-    let mut quiz = quiz.lock().await;
-    let instance = quiz.get_from_category(user, category).await?;
-    drop(quiz);
-    success(instance)
+    Extension(schedule): Extension<EventSchedule>,
+) -> Payload<IssuedQuestion> {
+    let context = schedule.of_user(user).await?;
+    context.ensure_running()?;
+    let instance = context.quiz.get_from_category(user, category).await?;
+    success(instance.into())
+}
+
+pub async fn use_fifty_fifty(
+    WithRejection(Path((user, question_id)), _): WithRejection<Path<(Uuid, Uuid)>, ServerError>,
+    Extension(schedule): Extension<EventSchedule>,
+) -> Payload<LifelineResponse> {
+    let context = schedule.of_user(user).await?;
+    context.ensure_running()?;
+    let (question, lifelines) = context.quiz.fifty_fifty(user, question_id).await?;
+    success(LifelineResponse {
+        question: question.into(),
+        lifelines,
+    })
+}
+
+pub async fn use_skip(
+    WithRejection(Path((user, question_id)), _): WithRejection<Path<(Uuid, Uuid)>, ServerError>,
+    Extension(schedule): Extension<EventSchedule>,
+) -> Payload<LifelineResponse> {
+    let context = schedule.of_user(user).await?;
+    context.ensure_running()?;
+    let (question, lifelines) = context.quiz.skip(user, question_id).await?;
+    success(LifelineResponse {
+        question: question.into(),
+        lifelines,
+    })
+}
+
+pub async fn get_hint(
+    WithRejection(Path(question_id), _): WithRejection<Path<Uuid>, ServerError>,
+    WithRejection(Query(query), _): WithRejection<Query<HintQuery>, ServerError>,
+    Extension(schedule): Extension<EventSchedule>,
+) -> Payload<Hint> {
+    let context = schedule.of_user(query.user).await?;
+    context.ensure_running()?;
+    success(context.quiz.hint(query.user, question_id).await?)
 }
 
 pub async fn answer_question(
-    WithRejection(Path((question_id, answer)), _): WithRejection<Path<(Uuid, u8)>, ServerError>,
-    Extension(quiz): Extension<Arc<Mutex<QuizHandler>>>,
+    WithRejection(Path((user, question_id)), _): WithRejection<Path<(Uuid, Uuid)>, ServerError>,
+    Extension(schedule): Extension<EventSchedule>,
+    Extension(pool): Extension<PgPool>,
+    Extension(events): Extension<EventBus>,
+    WithRejection(Json(answer), _): WithRejection<Json<Answer>, ServerError>,
 ) -> Payload<AnswerResponse> {
-    let mut quiz = quiz.lock().await;
-    let (correct, correct_answer) = quiz.answer(question_id, answer).await?;
-    drop(quiz);
+    let context = schedule.of_user(user).await?;
+    context.ensure_running()?;
+    let result = context.quiz.answer(user, question_id, answer).await?;
+    // the answer is already recorded at this point, so a failure here must not fail it
+    if result.correct && result.station.is_some() {
+        if let Err(err) = context.quest.progress(user).await {
+            log::error!("Could not update quest progress of user `{user}`: {err}");
+        }
+    }
+
+    let event = context.event.id;
+    events.publish(QuestEvent::QuestionAnswered {
+        event_id: event.clone(),
+        uuid: user,
+        question: question_id,
+        category: result.category.clone(),
+        correct: result.correct,
+        points: result.points,
+    });
+    if result.points > 0 {
+        match leaderboard(&pool, &event, None, LIVE_LEADERBOARD_SIZE, 0).await {
+            Ok(top) => events.publish(QuestEvent::LeaderboardChanged {
+                top,
+                event_id: event.clone(),
+            }),
+            Err(err) => log::error!("Could not load the leaderboard of event `{event}`: {err}"),
+        }
+    }
+    for achievement in &result.achievements {
+        events.publish(QuestEvent::AchievementEarned {
+            event_id: event.clone(),
+            uuid: user,
+            achievement: achievement.clone(),
+        });
+    }
+
     success(AnswerResponse {
-        correct,
-        correct_answer,
+        correct: result.correct,
+        correct_answer: result.correct_answer,
+        points: result.points,
+        streak: result.streak,
+        total: result.total,
+        explanation: result.explanation,
+        feedback: result.feedback,
+        achievements: result.achievements,
+    })
+}
+
+pub async fn get_user_achievements(
+    WithRejection(Path(user), _): WithRejection<Path<Uuid>, ServerError>,
+    Extension(schedule): Extension<EventSchedule>,
+) -> Payload<AchievementsResponse> {
+    let context = schedule.of_user(user).await?;
+    success(AchievementsResponse {
+        achievements: context.quiz.achievements(user).await?,
+    })
+}
+
+pub async fn get_user_score(
+    WithRejection(Path(user), _): WithRejection<Path<Uuid>, ServerError>,
+    Extension(pool): Extension<PgPool>,
+) -> Payload<ScoreResponse> {
+    if sqlx::query("SELECT * FROM users WHERE id = $1")
+        .bind(user)
+        .fetch_optional(&pool)
+        .await?
+        .is_none()
+    {
+        return err(ServerError::NotFound(format!(
+            "Could not find user with UUID of `{user}` in the database!"
+        )));
+    }
+    success(ScoreResponse {
+        total: user_score(&pool, user).await?,
+        categories: category_scores(&pool, user).await?,
+    })
+}
+
+pub async fn get_leaderboard(
+    WithRejection(Query(paging), _): WithRejection<Query<Paging>, ServerError>,
+    WithRejection(Query(query), _): WithRejection<Query<EventQuery>, ServerError>,
+    Extension(pool): Extension<PgPool>,
+    Extension(schedule): Extension<EventSchedule>,
+) -> Payload<LeaderboardResponse> {
+    let event = schedule.resolve(query.event.as_deref()).await?;
+    let entries = leaderboard(&pool, &event.id, None, paging.limit(), paging.offset()).await?;
+    success(LeaderboardResponse {
+        event: event.id,
+        category: None,
+        entries,
+    })
+}
+
+pub async fn get_category_leaderboard(
+    WithRejection(Path(category), _): WithRejection<Path<String>, ServerError>,
+    WithRejection(Query(paging), _): WithRejection<Query<Paging>, ServerError>,
+    WithRejection(Query(query), _): WithRejection<Query<EventQuery>, ServerError>,
+    Extension(pool): Extension<PgPool>,
+    Extension(schedule): Extension<EventSchedule>,
+) -> Payload<LeaderboardResponse> {
+    let event = schedule.resolve(query.event.as_deref()).await?;
+    let entries = leaderboard(
+        &pool,
+        &event.id,
+        Some(&category),
+        paging.limit(),
+        paging.offset(),
+    )
+    .await?;
+    success(LeaderboardResponse {
+        event: event.id,
+        category: Some(category),
+        entries,
+    })
+}
+
+pub async fn get_team(
+    WithRejection(Path(team), _): WithRejection<Path<Uuid>, ServerError>,
+    Extension(pool): Extension<PgPool>,
+) -> Payload<TeamResponse> {
+    let team = if let Some(team) = team_by_id(&pool, team).await? {
+        team
+    } else {
+        return err(ServerError::NotFound(format!(
+            "Could not find team with UUID of `{team}` in the database!"
+        )));
+    };
+    success(TeamResponse {
+        members: members(&pool, team.id).await?,
+        score: team_score(&pool, team.id).await?,
+        team,
+    })
+}
+
+pub async fn get_team_leaderboard(
+    WithRejection(Query(paging), _): WithRejection<Query<Paging>, ServerError>,
+    WithRejection(Query(query), _): WithRejection<Query<EventQuery>, ServerError>,
+    Extension(pool): Extension<PgPool>,
+    Extension(schedule): Extension<EventSchedule>,
+) -> Payload<TeamLeaderboardResponse> {
+    let event = schedule.resolve(query.event.as_deref()).await?;
+    let entries = team_leaderboard(&pool, &event.id, paging.limit(), paging.offset()).await?;
+    success(TeamLeaderboardResponse {
+        event: event.id,
+        entries,
+    })
+}
+
+pub async fn stream_events(
+    Extension(events): Extension<EventBus>,
+) -> Sse<impl Stream<Item = Result<Event, serde_json::Error>>> {
+    let stream = stream::unfold(events.subscribe(), |mut rx| async move {
+        loop {
+            match rx.recv().await {
+                Ok(event) => {
+                    let sse = Event::default().event(event.name()).json_data(&event);
+                    return Some((sse, rx));
+                }
+                Err(RecvError::Lagged(skipped)) => {
+                    log::warn!("Event stream subscriber lagged behind, skipped {skipped} events")
+                }
+                Err(RecvError::Closed) => return None,
+            }
+        }
+    });
+    Sse::new(stream).keep_alive(KeepAlive::default())
+}
+
+pub async fn validate_questions(
+    WithRejection(Query(query), _): WithRejection<Query<EventQuery>, ServerError>,
+    Extension(schedule): Extension<EventSchedule>,
+) -> Payload<ValidationResponse> {
+    let context = schedule
+        .context(schedule.resolve(query.event.as_deref()).await?)
+        .await?;
+    let categories = context.quiz.validate_all().await?;
+    success(ValidationResponse {
+        valid: categories.iter().all(|report| report.errors.is_empty()),
+        categories,
+    })
+}
+
+pub async fn get_categories(
+    WithRejection(Query(query), _): WithRejection<Query<CategoriesQuery>, ServerError>,
+    Extension(schedule): Extension<EventSchedule>,
+) -> Payload<CategoriesResponse> {
+    let context = match query.user {
+        Some(user) => schedule.of_user(user).await?,
+        None => {
+            schedule
+                .context(schedule.resolve(query.event.as_deref()).await?)
+                .await?
+        }
+    };
+    success(CategoriesResponse {
+        categories: context.quiz.categories(query.user).await?,
+    })
+}
+
+pub async fn tap_station(
+    WithRejection(Path((station, sha)), _): WithRejection<Path<(String, String)>, ServerError>,
+    Extension(pool): Extension<PgPool>,
+    Extension(schedule): Extension<EventSchedule>,
+) -> Payload<StationTapResponse> {
+    let context = schedule.active().await?;
+    context.ensure_running()?;
+    let station = match sqlx::query_as::<_, StoredStation>("SELECT * FROM stations WHERE id = $1")
+        .bind(&station)
+        .fetch_optional(&pool)
+        .await?
+    {
+        Some(station) => station,
+        None => {
+            return err(ServerError::NotFound(format!(
+                "Could not find station `{station}` in the database!"
+            )))
+        }
+    };
+    if !station.is_active(Utc::now()) {
+        return err(ServerError::StationInactive(station.id));
+    }
+    let user = match sqlx::query_as::<_, StoredUser>(
+        "SELECT * FROM users WHERE event_id = $1 AND card_hash = $2",
+    )
+    .bind(&context.event.id)
+    .bind(&sha)
+    .fetch_optional(&pool)
+    .await?
+    {
+        Some(user) => user,
+        None => {
+            return err(ServerError::NotFound(format!(
+                "Could not find user with SHA256 card hash of `{sha}` in the database!"
+            )))
+        }
+    };
+
+    let EventContext { quiz, quest, .. } = context;
+    quest.advance(user.id, &station.id).await?;
+    let visit = Uuid::new_v4();
+    sqlx::query("INSERT INTO station_visits VALUES ($1, $2, $3)")
+        .bind(visit)
+        .bind(&station.id)
+        .bind(user.id)
+        .execute(&pool)
+        .await?;
+
+    let question = quiz.get_from_station(user.id, &station).await;
+    let progress = quest.progress(user.id).await.unwrap_or_else(|err| {
+        log::error!(
+            "Could not evaluate quest progress of user `{}`: {err}",
+            user.id
+        );
+        None
+    });
+    success(StationTapResponse {
+        station: station.id,
+        station_name: station.name,
+        visit,
+        user: user.id,
+        question: question?.into(),
+        quest: progress,
+    })
+}
+
+pub async fn get_quest_progress(
+    WithRejection(Path(user), _): WithRejection<Path<Uuid>, ServerError>,
+    Extension(schedule): Extension<EventSchedule>,
+) -> Payload<QuestProgress> {
+    match schedule.of_user(user).await?.quest.progress(user).await? {
+        Some(progress) => success(progress),
+        None => err(ServerError::NotFound(
+            "There is no quest route configured".to_owned(),
+        )),
+    }
+}
+
+pub async fn get_active_event(
+    Extension(schedule): Extension<EventSchedule>,
+) -> Payload<EventResponse> {
+    success(schedule.resolve(None).await?.into())
+}
+
+pub async fn get_status(Extension(schedule): Extension<EventSchedule>) -> Payload<StatusResponse> {
+    success(StatusResponse {
+        event: schedule.resolve(None).await?.into(),
+        server_time: Utc::now(),
+    })
+}
+
+pub async fn get_event(
+    WithRejection(Path(event), _): WithRejection<Path<String>, ServerError>,
+    Extension(schedule): Extension<EventSchedule>,
+) -> Payload<EventResponse> {
+    success(schedule.resolve(Some(&event)).await?.into())
+}
+
+pub async fn get_events(Extension(schedule): Extension<EventSchedule>) -> Payload<EventsResponse> {
+    success(EventsResponse {
+        events: schedule
+            .events()
+            .await?
+            .into_iter()
+            .map(EventResponse::from)
+            .collect(),
+    })
+}
+
+pub async fn get_rewards(
+    WithRejection(Query(query), _): WithRejection<Query<EventQuery>, ServerError>,
+    Extension(pool): Extension<PgPool>,
+    Extension(schedule): Extension<EventSchedule>,
+) -> Payload<RewardsResponse> {
+    let event = schedule.resolve(query.event.as_deref()).await?;
+    success(RewardsResponse {
+        rewards: rewards(&pool, &event.id).await?,
+        event: event.id,
+    })
+}
+
+pub async fn redeem_reward(
+    WithRejection(Path((user, reward)), _): WithRejection<Path<(Uuid, String)>, ServerError>,
+    Extension(pool): Extension<PgPool>,
+    Extension(schedule): Extension<EventSchedule>,
+) -> Payload<RedemptionResponse> {
+    let event = schedule.of_user(user).await?.event;
+    if !event.can_redeem(Utc::now()) {
+        return err(ServerError::RedemptionClosed(event.id));
+    }
+    let redemption = redeem(&pool, &event.id, user, &reward).await?;
+    success(RedemptionResponse {
+        balance: balance(&pool, user).await?,
+        redemption,
+    })
+}
+
+pub async fn get_user_redemptions(
+    WithRejection(Path(user), _): WithRejection<Path<Uuid>, ServerError>,
+    Extension(pool): Extension<PgPool>,
+) -> Payload<RedemptionsResponse> {
+    success(RedemptionsResponse {
+        balance: Some(balance(&pool, user).await?),
+        redemptions: redemptions_of(&pool, user).await?,
+    })
+}
+
+pub async fn get_redemption_history(
+    WithRejection(Query(query), _): WithRejection<Query<EventQuery>, ServerError>,
+    Extension(pool): Extension<PgPool>,
+    Extension(schedule): Extension<EventSchedule>,
+) -> Payload<RedemptionsResponse> {
+    let event = schedule.resolve(query.event.as_deref()).await?;
+    success(RedemptionsResponse {
+        balance: None,
+        redemptions: redemption_history(&pool, &event.id).await?,
+    })
+}
+
+pub async fn verify_redemption(
+    WithRejection(Path(code), _): WithRejection<Path<String>, ServerError>,
+    WithRejection(Query(query), _): WithRejection<Query<VerifyQuery>, ServerError>,
+    Extension(pool): Extension<PgPool>,
+) -> Payload<RedemptionResponse> {
+    let redemption = verify(&pool, &code, query.staff.as_deref()).await?;
+    success(RedemptionResponse {
+        balance: balance(&pool, redemption.user_id).await?,
+        redemption,
     })
 }
@@ -1,7 +1,9 @@
 mod handlers;
 pub mod models;
 
+use crate::common::events::EventBus;
 use crate::common::questions::QuizHandler;
+use crate::common::schedule::EventSchedule;
 use crate::server::handlers::*;
 use crate::ServerConfig;
 use axum::http::{StatusCode, Uri};
@@ -10,25 +12,60 @@ use axum::{Extension, Router};
 use sqlx::PgPool;
 use std::net::SocketAddr;
 use std::str::FromStr;
-use std::sync::Arc;
-use tokio::sync::Mutex;
+use std::time::Duration;
 
 #[allow(unused_variables)]
-pub async fn init_server(cfg: &ServerConfig, pool: PgPool) -> anyhow::Result<()> {
+pub async fn init_server(cfg: &ServerConfig, pool: PgPool, events: EventBus) -> anyhow::Result<()> {
     let addr = SocketAddr::from_str(&format!("{}:{}", cfg.api.host, cfg.api.port))?;
     log::info!("Starting HTTP server on {}", addr);
 
-    let quiz = QuizHandler::new("questions");
+    let schedule = EventSchedule::new(pool.clone(), cfg.quiz.clone());
+    match schedule.active().await {
+        Ok(context) => log::info!("Active event is `{}`", context.event.id),
+        Err(err) => log::warn!("Could not load the active event: {err}"),
+    }
+    QuizHandler::collect_garbage(
+        pool.clone(),
+        Duration::from_secs(cfg.quiz.gc_interval),
+        Duration::from_secs(cfg.quiz.gc_grace_period),
+    );
 
     let app = Router::new()
         .route("/user/get/id/:id", get(get_user_id))
         .route("/user/get/sha/:hash", get(get_user_sha))
         .route("/user/register/:sha", post(begin_registration))
+        .route("/categories", get(get_categories))
         .route("/user/:user/question/:category", get(get_question))
-        .route("/quiz/answer/:question/:answer", post(answer_question))
+        .route("/user/:user/score", get(get_user_score))
+        .route("/user/:user/quest", get(get_quest_progress))
+        .route("/user/:user/achievements", get(get_user_achievements))
+        .route("/user/:user/redeem/:reward", post(redeem_reward))
+        .route("/user/:user/redemptions", get(get_user_redemptions))
+        .route("/rewards", get(get_rewards))
+        .route("/redemptions", get(get_redemption_history))
+        .route("/redemption/:code/verify", post(verify_redemption))
+        .route("/station/:station/tap/:sha", post(tap_station))
+        .route("/leaderboard", get(get_leaderboard))
+        .route("/leaderboard/:category", get(get_category_leaderboard))
+        .route("/team/:team", get(get_team))
+        .route("/teams/leaderboard", get(get_team_leaderboard))
+        .route("/events", get(stream_events))
+        .route("/status", get(get_status))
+        .route("/event", get(get_active_event))
+        .route("/event/list", get(get_events))
+        .route("/event/:event", get(get_event))
+        .route("/quiz/validate", get(validate_questions))
+        .route("/quiz/:question/hint", get(get_hint))
+        .route("/user/:user/answer/:question", post(answer_question))
+        .route(
+            "/user/:user/lifeline/fifty/:question",
+            post(use_fifty_fifty),
+        )
+        .route("/user/:user/lifeline/skip/:question", post(use_skip))
         .fallback(handler404)
         .layer(Extension(pool))
-        .layer(Extension(Arc::new(Mutex::new(quiz))));
+        .layer(Extension(events))
+        .layer(Extension(schedule));
 
     axum::Server::bind(&addr)
         .serve(app.into_make_service())
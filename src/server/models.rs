@@ -1,7 +1,17 @@
+use crate::common::achievements::AchievementInfo;
+use crate::common::lifelines::Lifelines;
+use crate::common::models::{EventStatus, StoredEvent};
+use crate::common::quest::QuestProgress;
+use crate::common::questions::{Answer, CategoryInfo, QuestionInstance, QuestionKind};
+use crate::common::rewards::{Redemption, Reward};
+use crate::common::scores::{CategoryScore, LeaderboardEntry, TeamLeaderboardEntry, UserScore};
+use crate::common::teams::{Team, TeamMember};
+use crate::common::validation::CategoryReport;
 use crate::server::handlers::ServerError;
 use axum::http::StatusCode;
 use axum::response::{IntoResponse, Response};
 use axum::Json;
+use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
 use uuid::Uuid;
 
@@ -51,6 +61,10 @@ pub struct UserData {
     pub username: String,
     pub card_hash: String,
     pub uuid: Uuid,
+    pub event: String,
+    pub score: UserScore,
+    pub team: Option<Team>,
+    pub lifelines: Lifelines,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -64,8 +78,389 @@ pub struct RegistrationResponse {
     pub bot_url: String,
 }
 
+/// Issued question as the user sees it, leaves out everything that gives the answer away
+#[derive(Debug, Clone, Serialize)]
+pub struct IssuedQuestion {
+    pub id: Uuid,
+    pub bound_to: Uuid,
+    pub category: String,
+    pub issued_at: DateTime<Utc>,
+    pub expires_at: Option<DateTime<Utc>>,
+    pub station: Option<String>,
+    pub question: String,
+    pub points: Option<u32>,
+    pub time_limit: Option<u64>,
+    /// Amount of hints the question has, they are revealed one by one
+    pub hints: usize,
+    pub hints_used: i32,
+    #[serde(flatten)]
+    pub kind: IssuedQuestionKind,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum IssuedQuestionKind {
+    Single { variants: Vec<String> },
+    Multiple { variants: Vec<String> },
+    Text,
+    Numeric,
+    Ordering { items: Vec<String> },
+}
+
+impl From<QuestionInstance> for IssuedQuestion {
+    fn from(instance: QuestionInstance) -> Self {
+        let question = instance.question;
+        Self {
+            id: instance.id,
+            bound_to: instance.bound_to,
+            category: instance.category,
+            issued_at: instance.issued_at,
+            expires_at: instance.expires_at,
+            station: instance.station,
+            question: question.question,
+            points: question.points,
+            time_limit: question.time_limit,
+            hints: question.hints.len(),
+            hints_used: instance.hints_used,
+            kind: match question.kind {
+                QuestionKind::Single(q) => IssuedQuestionKind::Single {
+                    variants: q.variants,
+                },
+                QuestionKind::Multiple(q) => IssuedQuestionKind::Multiple {
+                    variants: q.variants,
+                },
+                QuestionKind::Text(_) => IssuedQuestionKind::Text,
+                QuestionKind::Numeric(_) => IssuedQuestionKind::Numeric,
+                QuestionKind::Ordering(q) => IssuedQuestionKind::Ordering { items: q.items },
+            },
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct LifelineResponse {
+    #[serde(flatten)]
+    pub question: IssuedQuestion,
+    /// Lifelines the user has left
+    pub lifelines: Lifelines,
+}
+
 #[derive(Debug, Clone, Serialize)]
 pub struct AnswerResponse {
     pub correct: bool,
-    pub correct_answer: u8,
+    pub correct_answer: Answer,
+    pub points: u32,
+    /// Consecutive correct answers in the category of the question
+    pub streak: i32,
+    /// Points of the user after this answer
+    pub total: i64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub explanation: Option<String>,
+    /// Feedback on the picked variants
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub feedback: Vec<String>,
+    pub achievements: Vec<AchievementInfo>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct AchievementsResponse {
+    pub achievements: Vec<AchievementInfo>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ScoreResponse {
+    #[serde(flatten)]
+    pub total: UserScore,
+    pub categories: Vec<CategoryScore>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct Paging {
+    pub limit: Option<i64>,
+    pub offset: Option<i64>,
+}
+
+impl Paging {
+    pub const DEFAULT_LIMIT: i64 = 50;
+    pub const MAX_LIMIT: i64 = 200;
+
+    pub fn limit(&self) -> i64 {
+        self.limit
+            .unwrap_or(Self::DEFAULT_LIMIT)
+            .clamp(1, Self::MAX_LIMIT)
+    }
+
+    pub fn offset(&self) -> i64 {
+        self.offset.unwrap_or(0).max(0)
+    }
+}
+
+/// Selects the event of a read-only request, the active event is used if none is given
+#[derive(Debug, Clone, Deserialize)]
+pub struct EventQuery {
+    pub event: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct EventResponse {
+    #[serde(flatten)]
+    pub event: StoredEvent,
+    pub status: EventStatus,
+    /// Finished events can still be queried but no longer be played
+    pub read_only: bool,
+}
+
+impl From<StoredEvent> for EventResponse {
+    fn from(event: StoredEvent) -> Self {
+        let now = Utc::now();
+        Self {
+            status: event.status(now),
+            read_only: event.is_finished(now),
+            event,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct StatusResponse {
+    #[serde(flatten)]
+    pub event: EventResponse,
+    /// Lets terminals show countdowns without relying on their own clock
+    pub server_time: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct EventsResponse {
+    pub events: Vec<EventResponse>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct LeaderboardResponse {
+    pub event: String,
+    pub category: Option<String>,
+    pub entries: Vec<LeaderboardEntry>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ValidationResponse {
+    pub valid: bool,
+    pub categories: Vec<CategoryReport>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct HintQuery {
+    /// User asking for the hint, has to be the one the question was issued to
+    pub user: Uuid,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct CategoriesQuery {
+    pub user: Option<Uuid>,
+    pub event: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct CategoriesResponse {
+    pub categories: Vec<CategoryInfo>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct StationTapResponse {
+    pub station: String,
+    pub station_name: String,
+    pub visit: Uuid,
+    pub user: Uuid,
+    pub question: IssuedQuestion,
+    pub quest: Option<QuestProgress>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct TeamResponse {
+    #[serde(flatten)]
+    pub team: Team,
+    pub members: Vec<TeamMember>,
+    pub score: UserScore,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct TeamLeaderboardResponse {
+    pub event: String,
+    pub entries: Vec<TeamLeaderboardEntry>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct RewardsResponse {
+    pub event: String,
+    pub rewards: Vec<Reward>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct RedemptionResponse {
+    #[serde(flatten)]
+    pub redemption: Redemption,
+    /// Points the user has left to spend
+    pub balance: i64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct RedemptionsResponse {
+    /// Points the user has left to spend, only present for the history of a single user
+    pub balance: Option<i64>,
+    pub redemptions: Vec<Redemption>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct VerifyQuery {
+    /// Name of the staff member handing out the reward, kept for the audit history
+    pub staff: Option<String>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::common::lifelines::Lifelines;
+    use crate::common::questions::Question;
+    use serde_json::{json, Value};
+
+    /// Fields of the question model that give the answer away
+    const ANSWER_FIELDS: [&str; 8] = [
+        "correct_answer",
+        "correct_answers",
+        "accepted",
+        "answer",
+        "tolerance",
+        "correct_order",
+        "explanation",
+        "feedback",
+    ];
+
+    /// Texts of the fixtures below that may only be shown after answering, or on request
+    const SECRETS: [&str; 7] = [
+        "Not A",
+        "Only one",
+        "Because B",
+        "A is wrong",
+        "C is wrong",
+        "Two of them",
+        "Paris",
+    ];
+
+    fn instance(question: Value) -> QuestionInstance {
+        QuestionInstance {
+            id: Uuid::new_v4(),
+            bound_to: Uuid::new_v4(),
+            category: "test".to_owned(),
+            issued_at: Utc::now(),
+            expires_at: None,
+            station: None,
+            hints_used: 0,
+            question: serde_json::from_value::<Question>(question).unwrap(),
+        }
+    }
+
+    fn questions() -> Vec<Value> {
+        vec![
+            json!({
+                "type": "single",
+                "question": "Single?",
+                "variants": ["A", "B", "C"],
+                "correct_answer": 1,
+                "hints": ["Not A", "Only one"],
+                "explanation": "Because B",
+                "feedback": ["A is wrong", null, "C is wrong"],
+            }),
+            json!({
+                "type": "multiple",
+                "question": "Multiple?",
+                "variants": ["A", "B", "C"],
+                "correct_answers": [0, 2],
+                "hints": ["Two of them"],
+            }),
+            json!({
+                "type": "text",
+                "question": "Text?",
+                "accepted": ["Paris"],
+            }),
+            json!({
+                "type": "numeric",
+                "question": "Numeric?",
+                "answer": 42.0,
+                "tolerance": 0.5,
+            }),
+            json!({
+                "type": "ordering",
+                "question": "Ordering?",
+                "items": ["A", "B", "C"],
+                "correct_order": [2, 0, 1],
+            }),
+        ]
+    }
+
+    fn assert_no_answer_fields(value: &Value) {
+        let json = value.to_string();
+        for secret in SECRETS {
+            assert!(!json.contains(secret), "`{secret}` leaked into {json}");
+        }
+        assert_no_answer_keys(value);
+    }
+
+    fn assert_no_answer_keys(value: &Value) {
+        match value {
+            Value::Object(fields) => {
+                for (key, field) in fields {
+                    assert!(
+                        !ANSWER_FIELDS.contains(&key.as_str()),
+                        "`{key}` leaked into {value}"
+                    );
+                    if key == "hints" {
+                        assert!(field.is_u64(), "hint texts leaked into {value}");
+                    }
+                    assert_no_answer_keys(field);
+                }
+            }
+            Value::Array(items) => items.iter().for_each(assert_no_answer_keys),
+            _ => {}
+        }
+    }
+
+    #[test]
+    fn issued_question_has_no_answer_fields() {
+        for question in questions() {
+            let issued = IssuedQuestion::from(instance(question));
+            assert_no_answer_fields(&serde_json::to_value(issued).unwrap());
+        }
+    }
+
+    #[test]
+    fn issued_question_keeps_variants() {
+        let issued =
+            serde_json::to_value(IssuedQuestion::from(instance(questions().remove(0)))).unwrap();
+        assert_eq!(issued["type"], "single");
+        assert_eq!(issued["variants"], json!(["A", "B", "C"]));
+        assert_eq!(issued["hints"], 2);
+    }
+
+    #[test]
+    fn lifeline_and_station_responses_have_no_answer_fields() {
+        for question in questions() {
+            let lifeline = LifelineResponse {
+                question: instance(question.clone()).into(),
+                lifelines: Lifelines {
+                    fifty_fifty: 1,
+                    skip: 1,
+                },
+            };
+            assert_no_answer_fields(&serde_json::to_value(lifeline).unwrap());
+
+            let tap = StationTapResponse {
+                station: "station".to_owned(),
+                station_name: "Station".to_owned(),
+                visit: Uuid::new_v4(),
+                user: Uuid::new_v4(),
+                question: instance(question).into(),
+                quest: None,
+            };
+            assert_no_answer_fields(&serde_json::to_value(tap).unwrap());
+        }
+    }
 }
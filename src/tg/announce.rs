@@ -0,0 +1,88 @@
+use crate::common::achievements::AchievementInfo;
+use crate::common::events::{EventBus, QuestEvent};
+use sqlx::PgPool;
+use teloxide::prelude::*;
+use tokio::sync::broadcast::error::RecvError;
+use uuid::Uuid;
+
+/// Forwards quest events that concern a single player to their telegram chat
+pub async fn announce(bot: AutoSend<Bot>, pool: PgPool, events: EventBus) {
+    let mut rx = events.subscribe();
+    loop {
+        let result = match rx.recv().await {
+            Ok(QuestEvent::AchievementEarned {
+                uuid, achievement, ..
+            }) => announce_achievement(&bot, &pool, uuid, &achievement).await,
+            Ok(QuestEvent::QuestionAnswered {
+                question, correct, ..
+            }) => announce_explanation(&bot, &pool, question, correct).await,
+            Ok(_) => Ok(()),
+            Err(RecvError::Lagged(skipped)) => {
+                log::warn!("Telegram announcer lagged behind, skipped {skipped} events");
+                Ok(())
+            }
+            Err(RecvError::Closed) => return,
+        };
+        if let Err(err) = result {
+            log::error!("Could not send announcement: {err}");
+        }
+    }
+}
+
+/// Sends the explanation of an answered question, if it has one
+async fn announce_explanation(
+    bot: &AutoSend<Bot>,
+    pool: &PgPool,
+    question: Uuid,
+    correct: bool,
+) -> anyhow::Result<()> {
+    let found = sqlx::query_as::<_, (Option<i64>, String, Option<String>)>(
+        "SELECT u.telegram_id, q.question->>'question', q.question->>'explanation' \
+         FROM question_instances q JOIN users u ON u.id = q.bound_to WHERE q.id = $1",
+    )
+    .bind(question)
+    .fetch_optional(pool)
+    .await?;
+    let (chat, question, explanation) = match found {
+        Some((Some(chat), question, Some(explanation))) => (ChatId(chat), question, explanation),
+        _ => return Ok(()),
+    };
+
+    let verdict = if correct {
+        "✅ Верно!"
+    } else {
+        "❌ Неверно."
+    };
+    bot.send_message(chat, format!("{verdict} «{question}»\n{explanation}"))
+        .await?;
+    Ok(())
+}
+
+async fn announce_achievement(
+    bot: &AutoSend<Bot>,
+    pool: &PgPool,
+    user: Uuid,
+    achievement: &AchievementInfo,
+) -> anyhow::Result<()> {
+    let chat: Option<i64> = sqlx::query_scalar("SELECT telegram_id FROM users WHERE id = $1")
+        .bind(user)
+        .fetch_optional(pool)
+        .await?
+        .flatten();
+    let chat = match chat {
+        Some(chat) => ChatId(chat),
+        None => return Ok(()),
+    };
+
+    let mut text = format!(
+        "{} Новое достижение: {}",
+        achievement.icon.as_deref().unwrap_or("🏆"),
+        achievement.title
+    );
+    if let Some(description) = &achievement.description {
+        text.push('\n');
+        text.push_str(description);
+    }
+    bot.send_message(chat, text).await?;
+    Ok(())
+}
@@ -1,6 +1,15 @@
+pub mod announce;
 pub mod register;
+pub mod rewards;
+pub mod status;
+pub mod team;
 
+use crate::common::events::EventBus;
+use crate::common::models::{StoredEvent, StoredUser};
+use crate::common::schedule::active_event;
+use crate::tg::announce::announce;
 use crate::tg::register::{schema, DialogueState};
+use crate::TeamConfig;
 use sqlx::PgPool;
 use teloxide::dispatching::dialogue::InMemStorage;
 use teloxide::{prelude::*, utils::command::BotCommands};
@@ -12,24 +21,79 @@ pub enum Command {
     Help,
     #[command(description = "Показывает основную информацию про этого бота.")]
     Start,
+    #[command(description = "Показывает, идёт ли сейчас квест.")]
+    Status,
     #[command(description = "Начинает процесс регистрации. Берет токен регистрации как аргумент.")]
     Register(String),
     #[command(description = "Отменяет процесс регистрации")]
     Cancel,
+    #[command(
+        description = "Управление командой: `create <название>`, `join <код>`, `leave`, `kick <ник>`."
+    )]
+    Team(String),
+    #[command(
+        description = "Без аргумента показывает призы и ваш баланс, с названием приза обменивает на него очки."
+    )]
+    Redeem(String),
 }
 
 type SignupDialogue = Dialogue<DialogueState, InMemStorage<DialogueState>>;
 
-pub async fn init_tg(tk: String, pool: PgPool) -> anyhow::Result<()> {
+pub async fn init_tg(
+    tk: String,
+    pool: PgPool,
+    events: EventBus,
+    teams: TeamConfig,
+) -> anyhow::Result<()> {
     log::info!("Starting telegram bot...");
 
     let bot = Bot::new(tk).auto_send();
+    tokio::spawn(announce(bot.clone(), pool.clone(), events.clone()));
 
     Dispatcher::builder(bot, schema())
-        .dependencies(dptree::deps![InMemStorage::<DialogueState>::new(), pool])
+        .dependencies(dptree::deps![
+            InMemStorage::<DialogueState>::new(),
+            pool,
+            events,
+            teams
+        ])
         .enable_ctrlc_handler()
         .build()
         .dispatch()
         .await;
     Ok(())
 }
+
+/// Resolves the user the message was sent by in the active event. Tells them what is wrong and
+/// returns `None` if there is no active event or they are not registered in it.
+pub async fn current_user(
+    bot: &AutoSend<Bot>,
+    msg: &Message,
+    pool: &PgPool,
+) -> anyhow::Result<Option<(StoredEvent, StoredUser)>> {
+    let event = if let Some(event) = active_event(pool).await? {
+        event
+    } else {
+        bot.send_message(msg.chat.id, "Сейчас нет активного квеста.")
+            .await?;
+        return Ok(None);
+    };
+    let user = if let Some(user) = sqlx::query_as::<_, StoredUser>(
+        "SELECT * FROM users WHERE event_id = $1 AND telegram_id = $2",
+    )
+    .bind(&event.id)
+    .bind(msg.chat.id.0)
+    .fetch_optional(pool)
+    .await?
+    {
+        user
+    } else {
+        bot.send_message(
+            msg.chat.id,
+            "Сначала зарегистрируйтесь на квест командой /register.",
+        )
+        .await?;
+        return Ok(None);
+    };
+    Ok(Some((event, user)))
+}
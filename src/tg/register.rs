@@ -1,5 +1,11 @@
+use crate::common::events::{EventBus, QuestEvent};
 use crate::common::models::UserRegStage;
+use crate::common::schedule::active_event;
+use crate::tg::rewards::redeem;
+use crate::tg::status::status;
+use crate::tg::team::team;
 use crate::tg::{Command, SignupDialogue};
+use chrono::Utc;
 use sqlx::PgPool;
 use teloxide::dispatching::dialogue::InMemStorage;
 use teloxide::dispatching::{dialogue, UpdateHandler};
@@ -12,9 +18,14 @@ use uuid::Uuid;
 #[derive(Debug, Clone)]
 pub enum DialogueState {
     Start,
-    GetUsername { id: Uuid, card_hash: String },
+    GetUsername {
+        id: Uuid,
+        card_hash: String,
+        event_id: String,
+    },
 }
 
+#[allow(clippy::derivable_impls)]
 impl Default for DialogueState {
     fn default() -> Self {
         DialogueState::Start
@@ -29,14 +40,27 @@ pub fn schema() -> UpdateHandler<anyhow::Error> {
                 .branch(case![Command::Register(token)].endpoint(register)),
         )
         .branch(case![Command::Help].endpoint(help))
+        .branch(case![Command::Status].endpoint(status))
+        .branch(case![Command::Team(args)].endpoint(team))
+        .branch(case![Command::Redeem(reward)].endpoint(redeem))
         .branch(case![Command::Cancel].endpoint(cancel));
 
     let message_handler = Update::filter_message().branch(command_handler).branch(
-        case![DialogueState::GetUsername { id, card_hash }].endpoint(receive_username_text),
+        case![DialogueState::GetUsername {
+            id,
+            card_hash,
+            event_id
+        }]
+        .endpoint(receive_username_text),
     );
 
     let callback_query_handler = Update::filter_callback_query().branch(
-        case![DialogueState::GetUsername { id, card_hash }].endpoint(receive_username_callback),
+        case![DialogueState::GetUsername {
+            id,
+            card_hash,
+            event_id
+        }]
+        .endpoint(receive_username_callback),
     );
 
     dialogue::enter::<Update, InMemStorage<DialogueState>, DialogueState, _>()
@@ -62,10 +86,11 @@ async fn receive_username_callback(
     q: CallbackQuery,
     dialogue: SignupDialogue,
     pool: PgPool,
-    (id, card_hash): (Uuid, String),
+    events: EventBus,
+    (id, card_hash, event_id): (Uuid, String, String),
 ) -> anyhow::Result<()> {
     if let Some(username) = &q.data {
-        if user_exists(&pool, username).await? {
+        if user_exists(&pool, &event_id, username).await? {
             bot.send_message(dialogue.chat_id(), format!("Пользователь с ником `{}` уже существует\\!\nПожалуйста, выберите другой ник\\.", username)).parse_mode(ParseMode::MarkdownV2).await?;
             return Ok(());
         }
@@ -83,9 +108,13 @@ async fn receive_username_callback(
             bot,
             dialogue.chat_id(),
             pool,
+            events,
             username.to_owned(),
-            id,
-            card_hash,
+            UserRegStage {
+                id,
+                hash: card_hash,
+                event_id,
+            },
         )
         .await?;
 
@@ -100,11 +129,12 @@ async fn receive_username_text(
     msg: Message,
     dialogue: SignupDialogue,
     pool: PgPool,
-    (id, card_hash): (Uuid, String),
+    events: EventBus,
+    (id, card_hash, event_id): (Uuid, String, String),
 ) -> anyhow::Result<()> {
     match msg.text().map(ToOwned::to_owned) {
         Some(username) => {
-            if user_exists(&pool, &username).await? {
+            if user_exists(&pool, &event_id, &username).await? {
                 bot.send_message(dialogue.chat_id(), format!("Пользователь с ником `{}` уже существует\\!\nПожалуйста, выберите другой ник\\.", username)).parse_mode(ParseMode::MarkdownV2).await?;
                 return Ok(());
             }
@@ -114,7 +144,19 @@ async fn receive_username_text(
             )
             .parse_mode(ParseMode::MarkdownV2)
             .await?;
-            finish_registration(bot, msg.chat.id, pool, username, id, card_hash).await?;
+            finish_registration(
+                bot,
+                msg.chat.id,
+                pool,
+                events,
+                username,
+                UserRegStage {
+                    id,
+                    hash: card_hash,
+                    event_id,
+                },
+            )
+            .await?;
             dialogue.exit().await?;
         }
         None => {
@@ -152,11 +194,30 @@ pub async fn register(
             .await?;
         return Ok(());
     }
-    let stage = if let Some(stage) =
-        sqlx::query_as::<_, UserRegStage>("SELECT * FROM users_reg WHERE starts_with(hash, $1)")
-            .bind(token)
-            .fetch_optional(&pool)
-            .await?
+    let event = match active_event(&pool).await? {
+        Some(event) if !event.is_finished(Utc::now()) => event,
+        _ => {
+            bot.send_message(msg.chat.id, "Сейчас нет активного квеста.")
+                .await?;
+            return Ok(());
+        }
+    };
+    if telegram_registered(&pool, &event.id, msg.chat.id).await? {
+        bot.send_message(
+            msg.chat.id,
+            "Этот Telegram аккаунт уже зарегистрирован на квест!",
+        )
+        .await?;
+        dialogue.exit().await?;
+        return Ok(());
+    }
+    let stage = if let Some(stage) = sqlx::query_as::<_, UserRegStage>(
+        "SELECT * FROM users_reg WHERE event_id = $1 AND starts_with(hash, $2)",
+    )
+    .bind(&event.id)
+    .bind(token)
+    .fetch_optional(&pool)
+    .await?
     {
         stage
     } else {
@@ -168,7 +229,8 @@ pub async fn register(
     bot.send_message(msg.chat.id, "Вы начинаете регистрацию на квест.")
         .await?;
 
-    let deleted = sqlx::query("DELETE FROM users_reg WHERE starts_with(hash, $1)")
+    let deleted = sqlx::query("DELETE FROM users_reg WHERE event_id = $1 AND hash = $2")
+        .bind(&stage.event_id)
         .bind(&stage.hash)
         .execute(&pool)
         .await?;
@@ -188,14 +250,16 @@ pub async fn register(
         .update(DialogueState::GetUsername {
             id: stage.id,
             card_hash: stage.hash,
+            event_id: stage.event_id,
         })
         .await?;
 
     Ok(())
 }
 
-async fn user_exists(pool: &PgPool, username: &String) -> anyhow::Result<bool> {
-    if sqlx::query("SELECT * FROM users WHERE username = $1")
+async fn user_exists(pool: &PgPool, event_id: &str, username: &String) -> anyhow::Result<bool> {
+    if sqlx::query("SELECT * FROM users WHERE event_id = $1 AND username = $2")
+        .bind(event_id)
         .bind(username)
         .fetch_optional(pool)
         .await?
@@ -207,27 +271,49 @@ async fn user_exists(pool: &PgPool, username: &String) -> anyhow::Result<bool> {
     }
 }
 
+async fn telegram_registered(pool: &PgPool, event_id: &str, id: ChatId) -> anyhow::Result<bool> {
+    Ok(
+        sqlx::query("SELECT id FROM users WHERE event_id = $1 AND telegram_id = $2")
+            .bind(event_id)
+            .bind(id.0)
+            .fetch_optional(pool)
+            .await?
+            .is_some(),
+    )
+}
+
 #[allow(unused_variables)]
 async fn finish_registration(
     bot: AutoSend<Bot>,
     id: ChatId,
     pool: PgPool,
+    events: EventBus,
     username: String,
-    uuid: Uuid,
-    card_hash: String,
+    stage: UserRegStage,
 ) -> anyhow::Result<()> {
-    let rows = sqlx::query("INSERT INTO users VALUES($1, $2, $3)")
-        .bind(card_hash)
-        .bind(uuid)
-        .bind(username)
-        .execute(&pool)
-        .await?;
+    let rows = sqlx::query(
+        "INSERT INTO users(card_hash, id, username, telegram_id, event_id) \
+         VALUES ($1, $2, $3, $4, $5)",
+    )
+    .bind(stage.hash)
+    .bind(stage.id)
+    .bind(&username)
+    .bind(id.0)
+    .bind(&stage.event_id)
+    .execute(&pool)
+    .await?;
     if rows.rows_affected() < 1 {
         bot.send_message(id, "Не удалось провести регистрацию!")
             .await?;
         return Ok(());
     }
 
+    events.publish(QuestEvent::UserRegistered {
+        event_id: stage.event_id,
+        uuid: stage.id,
+        username,
+    });
+
     bot.send_message(id, "Регистрация проведена успешно!")
         .await?;
     Ok(())
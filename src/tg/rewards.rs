@@ -0,0 +1,53 @@
+use crate::common::rewards::{self, RewardError};
+use crate::tg::current_user;
+use chrono::Utc;
+use sqlx::PgPool;
+use teloxide::prelude::*;
+
+pub async fn redeem(
+    bot: AutoSend<Bot>,
+    msg: Message,
+    reward: String,
+    pool: PgPool,
+) -> anyhow::Result<()> {
+    let (event, user) = match current_user(&bot, &msg, &pool).await? {
+        Some(found) => found,
+        None => return Ok(()),
+    };
+    let reward = reward.trim();
+
+    if reward.is_empty() {
+        let balance = rewards::balance(&pool, user.id).await?;
+        let mut text = format!("Ваш баланс: {balance} очков.\n\nПризы:\n");
+        for reward in rewards::rewards(&pool, &event.id).await? {
+            text.push_str(&format!(
+                "- {} ({}): {} очков, осталось {}\n",
+                reward.name, reward.id, reward.cost, reward.stock
+            ));
+        }
+        text.push_str("\nЧтобы получить приз, отправьте /redeem <приз>.");
+        bot.send_message(msg.chat.id, text).await?;
+        return Ok(());
+    }
+
+    if !event.can_redeem(Utc::now()) {
+        bot.send_message(msg.chat.id, "Призы больше не выдаются.")
+            .await?;
+        return Ok(());
+    }
+    let text = match rewards::redeem(&pool, &event.id, user.id, reward).await {
+        Ok(redemption) => format!(
+            "Приз получен! Покажите этот код на стойке выдачи призов: {}\nОстаток: {} очков.",
+            redemption.code,
+            rewards::balance(&pool, user.id).await?
+        ),
+        Err(RewardError::UnknownReward(_)) => "Такого приза нет.".to_string(),
+        Err(RewardError::OutOfStock(_)) => "Этот приз закончился.".to_string(),
+        Err(RewardError::NotEnoughPoints { cost, balance }) => {
+            format!("Недостаточно очков: приз стоит {cost}, а у вас {balance}.")
+        }
+        Err(err) => return Err(err.into()),
+    };
+    bot.send_message(msg.chat.id, text).await?;
+    Ok(())
+}
@@ -0,0 +1,34 @@
+use crate::common::models::EventStatus;
+use crate::common::schedule::active_event;
+use chrono::Utc;
+use sqlx::PgPool;
+use teloxide::prelude::*;
+
+/// Format of start and end times shown to players
+const TIME_FORMAT: &str = "%d.%m.%Y %H:%M UTC";
+
+pub async fn status(bot: AutoSend<Bot>, msg: Message, pool: PgPool) -> anyhow::Result<()> {
+    let text = match active_event(&pool).await? {
+        None => "Сейчас нет запланированных квестов.".to_string(),
+        Some(event) => match event.status(Utc::now()) {
+            EventStatus::NotStarted => format!(
+                "Квест «{}» ещё не начался.\nНачало: {}\nОкончание: {}",
+                event.name,
+                event.starts_at.format(TIME_FORMAT),
+                event.ends_at.format(TIME_FORMAT)
+            ),
+            EventStatus::Running => format!(
+                "Квест «{}» идёт прямо сейчас!\nОкончание: {}",
+                event.name,
+                event.ends_at.format(TIME_FORMAT)
+            ),
+            EventStatus::Finished => format!(
+                "Квест «{}» завершён {}.",
+                event.name,
+                event.ends_at.format(TIME_FORMAT)
+            ),
+        },
+    };
+    bot.send_message(msg.chat.id, text).await?;
+    Ok(())
+}
@@ -0,0 +1,145 @@
+use crate::common::models::StoredUser;
+use crate::common::scores;
+use crate::common::teams::{self, TeamError};
+use crate::tg::current_user;
+use crate::TeamConfig;
+use chrono::Utc;
+use sqlx::PgPool;
+use teloxide::prelude::*;
+use teloxide::types::ParseMode;
+use teloxide::utils::markdown::{code_inline, escape};
+
+pub async fn team(
+    bot: AutoSend<Bot>,
+    msg: Message,
+    args: String,
+    pool: PgPool,
+    config: TeamConfig,
+) -> anyhow::Result<()> {
+    let (event, user) = match current_user(&bot, &msg, &pool).await? {
+        Some(found) => found,
+        None => return Ok(()),
+    };
+
+    let (action, argument) = args
+        .trim()
+        .split_once(char::is_whitespace)
+        .map(|(action, argument)| (action, argument.trim()))
+        .unwrap_or((args.trim(), ""));
+
+    if action.is_empty() {
+        return team_info(bot, msg, pool, user).await;
+    }
+    if event.is_finished(Utc::now()) {
+        bot.send_message(
+            msg.chat.id,
+            "Квест уже завершён, команды больше нельзя изменять.",
+        )
+        .await?;
+        return Ok(());
+    }
+
+    let result = match action {
+        "create" if !argument.is_empty() => {
+            teams::create_team(&pool, &config, &event.id, user.id, argument)
+                .await
+                .map(|team| {
+                    format!(
+                    "Команда {} создана\\!\nДругие участники могут вступить в неё командой {}\\.",
+                    code_inline(&team.name),
+                    code_inline(&format!("/team join {}", team.code))
+                )
+                })
+        }
+        "join" if !argument.is_empty() => {
+            teams::join_team(&pool, &config, &event.id, user.id, argument)
+                .await
+                .map(|team| format!("Вы вступили в команду {}\\!", code_inline(&team.name)))
+        }
+        "leave" => teams::leave_team(&pool, user.id)
+            .await
+            .map(|team| format!("Вы покинули команду {}\\.", code_inline(&team.name))),
+        "kick" if !argument.is_empty() => teams::kick_member(&pool, &config, user.id, argument)
+            .await
+            .map(|_| format!("{} исключён из команды\\.", code_inline(argument))),
+        _ => {
+            bot.send_message(
+                msg.chat.id,
+                "Использование: /team create <название>, /team join <код>, /team leave, /team kick <ник>",
+            )
+            .await?;
+            return Ok(());
+        }
+    };
+
+    let text = match result {
+        Ok(text) => text,
+        Err(TeamError::Sql(err)) => return Err(err.into()),
+        Err(err) => escape(&describe(&err, &config)),
+    };
+    bot.send_message(msg.chat.id, text)
+        .parse_mode(ParseMode::MarkdownV2)
+        .await?;
+    Ok(())
+}
+
+async fn team_info(
+    bot: AutoSend<Bot>,
+    msg: Message,
+    pool: PgPool,
+    user: StoredUser,
+) -> anyhow::Result<()> {
+    let team = if let Some(team) = teams::team_of(&pool, user.id).await? {
+        team
+    } else {
+        bot.send_message(
+            msg.chat.id,
+            "Вы не состоите в команде.\nСоздайте команду командой /team create <название> или вступите в существующую командой /team join <код>.",
+        )
+        .await?;
+        return Ok(());
+    };
+
+    let members = teams::members(&pool, team.id).await?;
+    let score = scores::team_score(&pool, team.id).await?;
+    let mut text = format!(
+        "Команда {}\nКод для вступления: {}\nОчки: {}\n\nУчастники:\n",
+        code_inline(&team.name),
+        code_inline(&team.code),
+        score.points
+    );
+    for member in members {
+        text.push_str(&escape(&format!(
+            "- {}{}\n",
+            member.username,
+            if member.captain {
+                " (капитан)"
+            } else {
+                ""
+            }
+        )));
+    }
+    bot.send_message(msg.chat.id, text)
+        .parse_mode(ParseMode::MarkdownV2)
+        .await?;
+    Ok(())
+}
+
+fn describe(err: &TeamError, config: &TeamConfig) -> String {
+    match err {
+        TeamError::AlreadyInTeam => "Вы уже состоите в команде.".to_string(),
+        TeamError::NotInTeam => "Вы не состоите в команде.".to_string(),
+        TeamError::NameTaken(name) => format!("Команда с названием `{name}` уже существует!"),
+        TeamError::InvalidName => {
+            "Название команды должно быть не длиннее 32 символов.".to_string()
+        }
+        TeamError::InvalidCode(_) => "Неверный код команды!".to_string(),
+        TeamError::TeamFull(_) => format!(
+            "В команде уже максимальное количество участников ({}).",
+            config.max_members
+        ),
+        TeamError::NotCaptain => "Это может сделать только капитан команды.".to_string(),
+        TeamError::UnknownMember(name) => format!("В вашей команде нет участника `{name}`."),
+        TeamError::Sql(err) => err.to_string(),
+    }
+}